@@ -0,0 +1,159 @@
+//! Building [`ConversionOptions`] one setting at a time
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{
+    AnimationOptions, BlenderExecutable, CancellationToken, ConversionOptions, Exporter,
+    GltfExportSettings, LightmapUv, OutputFormat, OutputLayout, OverwritePolicy, PostProcess,
+    Profile, PropertyValue, ProxyKind, PythonScript, RootTransform, SceneSelection,
+    ThumbnailOptions,
+};
+
+/// Builds [`ConversionOptions`], starting from the defaults. Each method sets the field of the
+/// same name, and setting an optional field sets it to `Some`.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::path::Path;
+/// use blend_converter::{ConversionOptions, OutputFormat};
+///
+/// let options = ConversionOptions::builder()
+///     .output_format(OutputFormat::GltfSeparate)
+///     .blender_path("/opt/blender/blender")
+///     .apply_modifiers(true)
+///     .build();
+/// options
+///     .convert_dir(Path::new("blends"), Path::new("gltfs"))
+///     .expect("failed to convert blends");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ConversionOptionsBuilder {
+    options: ConversionOptions,
+}
+
+/// Defines a setter for each field, wrapping the value in `Some` for optional fields. Types written
+/// as `impl Into<T>` take anything converting to `T`, such as a `&str` for a `PathBuf`.
+macro_rules! setters {
+    () => {};
+    ($(#[$attr:meta])* $field:ident: Option<$ty:ty>, $($rest:tt)*) => {
+        $(#[$attr])*
+        #[doc = concat!("Sets [`ConversionOptions::", stringify!($field), "`]")]
+        pub fn $field(mut self, $field: $ty) -> Self {
+            self.options.$field = Some($field.into());
+            self
+        }
+        setters!($($rest)*);
+    };
+    ($(#[$attr:meta])* $field:ident: $ty:ty, $($rest:tt)*) => {
+        $(#[$attr])*
+        #[doc = concat!("Sets [`ConversionOptions::", stringify!($field), "`]")]
+        pub fn $field(mut self, $field: $ty) -> Self {
+            self.options.$field = $field.into();
+            self
+        }
+        setters!($($rest)*);
+    };
+}
+
+impl ConversionOptionsBuilder {
+    setters! {
+        output_format: OutputFormat,
+        exporter: Option<Arc<dyn Exporter>>,
+        blender_path: Option<impl Into<PathBuf>>,
+        blender_executable: Option<BlenderExecutable>,
+        check_existing: bool,
+        apply_modifiers: bool,
+        extras: bool,
+        yup: bool,
+        profile: Profile,
+        gltf: GltfExportSettings,
+        atomic: bool,
+        batch: bool,
+        jobs: Option<usize>,
+        force: bool,
+        incremental: bool,
+        output_layout: OutputLayout,
+        overwrite: OverwritePolicy,
+        strip_world: bool,
+        unlit: bool,
+        image_quality: Option<u8>,
+        deterministic_ordering: bool,
+        lod_ratios: impl Into<Vec<f32>>,
+        root_transform: Option<RootTransform>,
+        collision_pattern: Option<impl Into<String>>,
+        scene_sidecar: bool,
+        manifest: bool,
+        exclude_cameras_and_lights: bool,
+        seed: Option<u64>,
+        respect_hide_render: bool,
+        visible_only: bool,
+        include_collections: impl Into<Vec<String>>,
+        exclude_name_patterns: impl Into<Vec<String>>,
+        required_property: Option<(String, PropertyValue)>,
+        material_remap: HashMap<String, String>,
+        triangulate: bool,
+        warn_on_ngons: bool,
+        flip_winding: bool,
+        nla_track_animations: bool,
+        animation: AnimationOptions,
+        user_resources_dir: Option<impl Into<PathBuf>>,
+        per_object: bool,
+        per_collection: bool,
+        export_scenes: SceneSelection,
+        scene: Option<impl Into<String>>,
+        proxy_geometry: Option<ProxyKind>,
+        lightmap_uv: Option<LightmapUv>,
+        render_thumbnail: Option<ThumbnailOptions>,
+        active_collection_only: bool,
+        skip_unchanged: bool,
+        embed_source: bool,
+        post_process: PostProcess,
+        source_version: Option<impl Into<String>>,
+        texture_search_dirs: impl Into<Vec<PathBuf>>,
+        require_textures: bool,
+        memory_limit: Option<u64>,
+        #[cfg(feature = "pty")]
+        pty: bool,
+        timeout: Option<Duration>,
+        cancellation: Option<CancellationToken>,
+        min_version: Option<(u32, u32, u32)>,
+        pre_export_script: Option<PythonScript>,
+        post_export_script: Option<PythonScript>,
+    }
+
+    /// The options that were set
+    pub fn build(self) -> ConversionOptions {
+        self.options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{ConversionOptions, OutputFormat};
+
+    #[test]
+    fn builder_matches_struct() {
+        let built = ConversionOptions::builder()
+            .output_format(OutputFormat::GltfSeparate)
+            .blender_path("blender")
+            .jobs(4)
+            .lod_ratios([0.5, 0.25])
+            .build();
+        let expected = ConversionOptions {
+            output_format: OutputFormat::GltfSeparate,
+            blender_path: Some(PathBuf::from("blender")),
+            jobs: Some(4),
+            lod_ratios: vec![0.5, 0.25],
+            ..Default::default()
+        };
+        assert_eq!(built, expected);
+        assert_eq!(built.clone(), built);
+        assert_ne!(built, ConversionOptions::default());
+    }
+}
@@ -0,0 +1,307 @@
+//! The manifest [`ConversionOptions::incremental`] keeps in the output directory to skip blends
+//! that have not changed
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+
+use crate::{ConversionOptions, Error, PythonScript};
+
+/// Name of the manifest file written to the output directory
+const CACHE_FILE_NAME: &str = ".blend_converter_cache.json";
+
+/// What the last conversions into an output directory were made from
+#[derive(Debug)]
+pub(crate) struct Cache {
+    path: PathBuf,
+    options: String,
+    blends: Map<String, Value>,
+}
+
+impl Cache {
+    /// Loads the manifest in `output_dir`. A missing or unreadable manifest, or one written with
+    /// different options, is treated as empty.
+    pub(crate) fn load(output_dir: &Path, options: &ConversionOptions) -> Self {
+        let path = output_dir.join(CACHE_FILE_NAME);
+        let options = options_hash(options);
+        let blends = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<Value>(&bytes).ok())
+            .filter(|manifest| manifest["options"] == options.as_str())
+            .and_then(|mut manifest| match manifest["blends"].take() {
+                Value::Object(blends) => Some(blends),
+                _ => None,
+            })
+            .unwrap_or_default();
+        Self {
+            path,
+            options,
+            blends,
+        }
+    }
+
+    /// Whether the blend recorded as `key` had the contents `hash` when it was last converted to
+    /// `output`, every file written then still exists and the libraries it linked are unchanged
+    pub(crate) fn is_fresh(&self, key: &str, hash: &str, output: &Path) -> bool {
+        let Some(entry) = self.blends.get(key) else {
+            return false;
+        };
+        let dependencies_unchanged = entry["dependencies"]
+            .as_object()
+            .is_none_or(|dependencies| {
+                dependencies.iter().all(|(path, hash)| {
+                    content_hash(Path::new(path)).ok().as_deref() == hash.as_str()
+                })
+            });
+        entry["hash"] == hash
+            && entry["output"] == *output.to_string_lossy()
+            && dependencies_unchanged
+            && entry["outputs"].as_array().is_some_and(|outputs| {
+                outputs
+                    .iter()
+                    .all(|output| output.as_str().is_some_and(|p| Path::new(p).is_file()))
+            })
+    }
+
+    /// Records that the blend `key` with the contents `hash` was converted to `output`, writing
+    /// `outputs` and linking the libraries `dependencies`. Libraries that can not be read are
+    /// recorded as missing.
+    pub(crate) fn insert(
+        &mut self,
+        key: String,
+        hash: &str,
+        output: &Path,
+        outputs: &[PathBuf],
+        dependencies: &[PathBuf],
+    ) {
+        let outputs: Vec<_> = outputs.iter().map(|p| p.to_string_lossy()).collect();
+        let dependencies: Map<String, Value> = dependencies
+            .iter()
+            .map(|path| {
+                let hash = content_hash(path).ok();
+                (path.to_string_lossy().into_owned(), json!(hash))
+            })
+            .collect();
+        self.blends.insert(
+            key,
+            json!({
+                "hash": hash,
+                "output": output.to_string_lossy(),
+                "outputs": outputs,
+                "dependencies": dependencies,
+            }),
+        );
+    }
+
+    /// The libraries linked by every blend in the cache
+    pub(crate) fn dependencies(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.blends
+            .values()
+            .filter_map(|entry| entry["dependencies"].as_object())
+            .flat_map(|dependencies| dependencies.keys().map(PathBuf::from))
+    }
+
+    /// Forgets every blend not in `keys`, such as blends that were deleted
+    pub(crate) fn retain(&mut self, keys: &[String]) {
+        self.blends.retain(|key, _| keys.contains(key));
+    }
+
+    pub(crate) fn save(&self) -> Result<(), Error> {
+        let manifest = json!({ "options": self.options, "blends": self.blends });
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_vec_pretty(&manifest)?)?;
+        Ok(())
+    }
+}
+
+/// Hash of the options that affect what a conversion writes, so changing them invalidates the
+/// cache. The crate version is included as the generated scripts change between versions.
+///
+/// The options are hashed through their debug output with `material_remap` sorted, as map order
+/// differs between runs, and with the contents of script files which blender reads when it runs.
+/// Options that only change how a conversion runs, not what it writes, are left out. A custom
+/// [`crate::OutputLayout`] is covered by recording each blend's output path instead, and a custom
+/// [`crate::Exporter`] only through its debug output.
+fn options_hash(options: &ConversionOptions) -> String {
+    let canonical = ConversionOptions {
+        force: false,
+        incremental: false,
+        cancellation: None,
+        batch: false,
+        jobs: None,
+        atomic: false,
+        check_existing: false,
+        overwrite: Default::default(),
+        timeout: None,
+        memory_limit: None,
+        #[cfg(feature = "pty")]
+        pty: false,
+        material_remap: HashMap::new(),
+        ..options.clone()
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION"));
+    hasher.update(format!("{canonical:?}"));
+    let remap: BTreeMap<_, _> = options.material_remap.iter().collect();
+    hasher.update(format!("{remap:?}"));
+    for script in [&options.pre_export_script, &options.post_export_script] {
+        if let Some(PythonScript::File { path }) = script {
+            let hash = content_hash(path).ok();
+            hasher.update(format!("{hash:?}"));
+        }
+    }
+    hex(hasher)
+}
+
+/// Hash of the contents of the file at `path`
+pub(crate) fn content_hash(path: &Path) -> Result<String, Error> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            return Ok(hex(hasher));
+        }
+        hasher.update(&buf[..read]);
+    }
+}
+
+/// The finished hash as lowercase hex. SHA-256 is used as, unlike the standard library's hasher,
+/// it is the same across Rust releases so the manifest stays valid.
+fn hex(hasher: Sha256) -> String {
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cache;
+    use crate::ConversionOptions;
+
+    #[test]
+    fn cache_is_invalidated_by_options() {
+        let dir = std::env::temp_dir().join("blend_converter_cache");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("create dir");
+        let output = dir.join("a.glb");
+        std::fs::write(&output, "").expect("write output");
+
+        let options = ConversionOptions::default();
+        let mut cache = Cache::load(&dir, &options);
+        assert!(!cache.is_fresh("a.blend", "1", &output));
+        cache.insert(
+            "a.blend".to_owned(),
+            "1",
+            &output,
+            std::slice::from_ref(&output),
+            &[],
+        );
+        cache.save().expect("save cache");
+
+        let cache = Cache::load(&dir, &options);
+        assert!(cache.is_fresh("a.blend", "1", &output));
+        assert!(!cache.is_fresh("a.blend", "2", &output));
+        // Options that do not change the output keep the cache
+        let forced = ConversionOptions {
+            force: true,
+            jobs: Some(4),
+            atomic: true,
+            check_existing: true,
+            overwrite: crate::OverwritePolicy::Skip,
+            timeout: Some(std::time::Duration::from_secs(60)),
+            memory_limit: Some(1 << 30),
+            ..Default::default()
+        };
+        assert!(Cache::load(&dir, &forced).is_fresh("a.blend", "1", &output));
+        let changed = ConversionOptions {
+            yup: false,
+            ..Default::default()
+        };
+        assert!(!Cache::load(&dir, &changed).is_fresh("a.blend", "1", &output));
+        // Exporting somewhere else, such as after changing a custom layout, converts again
+        assert!(!Cache::load(&dir, &options).is_fresh("a.blend", "1", &dir.join("b.glb")));
+        std::fs::remove_file(&output).expect("remove output");
+        assert!(!Cache::load(&dir, &options).is_fresh("a.blend", "1", &output));
+        std::fs::write(&output, "").expect("write output");
+
+        // Map order differs between maps so the remap must be hashed in a fixed order
+        let remapped = || ConversionOptions {
+            material_remap: (0..16)
+                .map(|i| (format!("from{i}"), format!("to{i}")))
+                .collect(),
+            ..Default::default()
+        };
+        let mut cache = Cache::load(&dir, &remapped());
+        cache.insert(
+            "a.blend".to_owned(),
+            "1",
+            &output,
+            std::slice::from_ref(&output),
+            &[],
+        );
+        cache.save().expect("save cache");
+        assert!(Cache::load(&dir, &remapped()).is_fresh("a.blend", "1", &output));
+
+        // Editing a script file changes what the conversion does
+        let script = dir.join("post.py");
+        std::fs::write(&script, "print(1)").expect("write script");
+        let scripted = ConversionOptions {
+            post_export_script: Some(crate::PythonScript::File {
+                path: script.clone(),
+            }),
+            ..Default::default()
+        };
+        let mut cache = Cache::load(&dir, &scripted);
+        cache.insert(
+            "a.blend".to_owned(),
+            "1",
+            &output,
+            std::slice::from_ref(&output),
+            &[],
+        );
+        cache.save().expect("save cache");
+        assert!(Cache::load(&dir, &scripted).is_fresh("a.blend", "1", &output));
+        std::fs::write(&script, "print(2)").expect("edit script");
+        assert!(!Cache::load(&dir, &scripted).is_fresh("a.blend", "1", &output));
+
+        std::fs::remove_dir_all(&dir).expect("cleanup");
+    }
+
+    #[test]
+    fn cache_is_invalidated_by_libraries() {
+        let dir = std::env::temp_dir().join("blend_converter_cache_libraries");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("create dir");
+        let output = dir.join("a.glb");
+        std::fs::write(&output, "").expect("write output");
+        let library = dir.join("lib.blend");
+        std::fs::write(&library, "v1").expect("write library");
+
+        let options = ConversionOptions::default();
+        let mut cache = Cache::load(&dir, &options);
+        cache.insert(
+            "a.blend".to_owned(),
+            "1",
+            &output,
+            std::slice::from_ref(&output),
+            std::slice::from_ref(&library),
+        );
+        assert!(cache.is_fresh("a.blend", "1", &output));
+        assert_eq!(
+            cache.dependencies().collect::<Vec<_>>(),
+            std::slice::from_ref(&library)
+        );
+        std::fs::write(&library, "v2").expect("edit library");
+        assert!(!cache.is_fresh("a.blend", "1", &output));
+        std::fs::remove_dir_all(&dir).expect("cleanup");
+    }
+}
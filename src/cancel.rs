@@ -0,0 +1,117 @@
+//! Cancelling conversions, from another thread with a [`CancellationToken`] or by dropping the
+//! future of an async method
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[cfg(feature = "tokio")]
+use crate::Error;
+
+/// A flag shared between threads that stops the conversions using it, see
+/// [`ConversionOptions::cancellation`](crate::ConversionOptions::cancellation). Clones share the
+/// same flag.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::path::Path;
+/// use blend_converter::{CancellationToken, ConversionOptions};
+///
+/// let token = CancellationToken::new();
+/// let options = ConversionOptions {
+///     cancellation: Some(token.clone()),
+///     ..Default::default()
+/// };
+/// let conversion = std::thread::spawn(move || {
+///     options.convert_dir(Path::new("blends"), Path::new("gltfs"))
+/// });
+/// // Stop converting if it takes too long
+/// std::thread::sleep(std::time::Duration::from_secs(600));
+/// token.cancel();
+/// match conversion.join().expect("conversion thread panicked") {
+///     Err(blend_converter::Error::Cancelled) => println!("cancelled"),
+///     result => println!("finished {:?}", result.map(|blends| blends.len())),
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// A token that has not been cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Kills the blender processes of the conversions using this token and stops them starting
+    /// any more
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether [`CancellationToken::cancel`] has been called
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Tokens are equal when they share a flag, that is when one is a clone of the other
+impl PartialEq for CancellationToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for CancellationToken {}
+
+thread_local! {
+    /// Set while a thread runs a conversion for an async method, see [`run_blocking`]
+    static CANCEL_TOKEN: RefCell<Option<CancellationToken>> = const { RefCell::new(None) };
+}
+
+/// The cancellation token of the async conversion running on this thread, if there is one
+pub(crate) fn cancel_token() -> Option<CancellationToken> {
+    CANCEL_TOKEN.with(|token| token.borrow().clone())
+}
+
+/// Runs `f` with `token` as this thread's cancellation token, so threads started by a conversion
+/// can be cancelled along with it
+pub(crate) fn with_cancel_token<T>(token: Option<CancellationToken>, f: impl FnOnce() -> T) -> T {
+    /// Clears the token even if `f` panics as blocking threads are reused
+    struct Reset;
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            CANCEL_TOKEN.with(|token| token.borrow_mut().take());
+        }
+    }
+    CANCEL_TOKEN.with(|current| *current.borrow_mut() = token);
+    let _reset = Reset;
+    f()
+}
+
+/// Sets the flag when dropped, which happens when the future awaiting the conversion is dropped
+#[cfg(feature = "tokio")]
+struct CancelOnDrop(CancellationToken);
+
+#[cfg(feature = "tokio")]
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+/// Runs the blocking conversion `f` on tokio's blocking thread pool. Dropping the returned future
+/// kills the running blender and stops `f` from starting another.
+#[cfg(feature = "tokio")]
+pub(crate) async fn run_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, Error> + Send + 'static,
+) -> Result<T, Error> {
+    let cancel = CancelOnDrop(CancellationToken::new());
+    let token = Some(cancel.0.clone());
+    let result = tokio::task::spawn_blocking(move || with_cancel_token(token, f)).await;
+    drop(cancel);
+    match result {
+        Ok(result) => result,
+        Err(err) => std::panic::resume_unwind(err.into_panic()),
+    }
+}
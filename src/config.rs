@@ -0,0 +1,184 @@
+//! Loading conversions from a `blend_converter.toml` config file
+
+use std::path::{Path, PathBuf};
+
+use crate::{ConversionOptions, ConvertedBlend, Error, PythonScript};
+
+/// A directory conversion described by a toml config file such as
+///
+/// ```toml
+/// input_dir = "blends"
+/// output_dir = "gltfs"
+///
+/// [options]
+/// output_format = "gltf_separate"
+/// apply_modifiers = true
+/// ```
+///
+/// The `options` table accepts the fields of [`ConversionOptions`] using their Rust names, any
+/// field left out takes its default value. Unknown fields are rejected to catch typos. Relative
+/// paths in the options, for script files, `user_resources_dir` and `texture_search_dirs`, are
+/// relative to the config file like the directories.
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Directory containing the blends to convert. Relative paths are relative to the config file.
+    pub input_dir: PathBuf,
+    /// Directory to write the converted files to. Relative paths are relative to the config file.
+    pub output_dir: PathBuf,
+    /// How the blends should be converted
+    #[serde(default)]
+    pub options: ConversionOptions,
+}
+
+impl Config {
+    /// The conventional config file name
+    pub const FILE_NAME: &'static str = "blend_converter.toml";
+
+    /// Reads and parses a config file, resolving relative paths against its directory
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let contents = std::fs::read_to_string(path)?;
+        let mut config: Self = toml::from_str(&contents)?;
+        config.resolve_paths(path.parent().unwrap_or(Path::new("")));
+        Ok(config)
+    }
+
+    /// Joins the relative paths of the config onto `base`
+    fn resolve_paths(&mut self, base: &Path) {
+        self.input_dir = base.join(&self.input_dir);
+        self.output_dir = base.join(&self.output_dir);
+        let options = &mut self.options;
+        for script in [
+            &mut options.pre_export_script,
+            &mut options.post_export_script,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if let PythonScript::File { path } = script {
+                *path = base.join(&*path);
+            }
+        }
+        if let Some(dir) = &mut options.user_resources_dir {
+            *dir = base.join(&*dir);
+        }
+        for dir in &mut options.texture_search_dirs {
+            *dir = base.join(&*dir);
+        }
+    }
+
+    /// Converts the input directory into the output directory using the options, see
+    /// [`ConversionOptions::convert_dir`]
+    pub fn convert(&self) -> Result<Vec<ConvertedBlend>, Error> {
+        self.options.convert_dir(&self.input_dir, &self.output_dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{OutputFormat, Profile, PythonScript};
+
+    #[test]
+    fn parse_config() {
+        let config: super::Config = toml::from_str(
+            r#"
+            input_dir = "blends"
+            output_dir = "gltfs"
+
+            [options]
+            output_format = "gltf_separate"
+            profile = "baseline"
+            yup = false
+            "#,
+        )
+        .expect("valid config");
+        assert!(matches!(
+            config.options.output_format,
+            OutputFormat::GltfSeparate
+        ));
+        assert!(matches!(config.options.profile, Profile::Baseline));
+        assert!(!config.options.yup);
+        assert!(!config.options.apply_modifiers);
+    }
+
+    #[test]
+    fn scripts_are_inline_or_files() {
+        let config: super::Config = toml::from_str(
+            r#"
+            input_dir = "blends"
+            output_dir = "gltfs"
+
+            [options]
+            pre_export_script = "bpy.ops.outliner.orphans_purge()"
+            post_export_script = { path = "hooks/post.py" }
+            "#,
+        )
+        .expect("valid config");
+        assert_eq!(
+            config.options.pre_export_script,
+            Some(PythonScript::Inline(
+                "bpy.ops.outliner.orphans_purge()".into()
+            ))
+        );
+        assert_eq!(
+            config.options.post_export_script,
+            Some(PythonScript::File {
+                path: "hooks/post.py".into()
+            })
+        );
+    }
+
+    #[test]
+    fn paths_are_relative_to_config() {
+        let mut config: super::Config = toml::from_str(
+            r#"
+            input_dir = "blends"
+            output_dir = "/abs/gltfs"
+
+            [options]
+            pre_export_script = "import bpy"
+            post_export_script = { path = "hooks/post.py" }
+            user_resources_dir = "resources"
+            texture_search_dirs = ["textures", "/abs/textures"]
+            "#,
+        )
+        .expect("valid config");
+        config.resolve_paths(Path::new("project"));
+        assert_eq!(config.input_dir, Path::new("project/blends"));
+        assert_eq!(config.output_dir, Path::new("/abs/gltfs"));
+        assert_eq!(
+            config.options.pre_export_script,
+            Some(PythonScript::Inline("import bpy".into()))
+        );
+        assert_eq!(
+            config.options.post_export_script,
+            Some(PythonScript::File {
+                path: "project/hooks/post.py".into()
+            })
+        );
+        assert_eq!(
+            config.options.user_resources_dir.as_deref(),
+            Some(Path::new("project/resources"))
+        );
+        assert_eq!(
+            config.options.texture_search_dirs,
+            [Path::new("project/textures"), Path::new("/abs/textures")]
+        );
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let result = toml::from_str::<super::Config>(
+            r#"
+            input_dir = "blends"
+            output_dir = "gltfs"
+
+            [options]
+            apply_modifers = true
+            "#,
+        );
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,535 @@
+//! Output formats and the python calls that export them
+
+use std::path::{Path, PathBuf};
+
+use crate::python::{format_py_bool, format_py_path, format_py_str, PyKwargs};
+use crate::{AnimationMode, ConversionOptions, SceneSelection};
+
+/// An exporter provides the python call that makes blender write the loaded blend in some format.
+/// The built in formats are provided by [`OutputFormat`], implement this to export a format this
+/// crate does not support and set it as [`ConversionOptions::exporter`].
+///
+/// When the call runs the generated script has already run every pre export step. The python
+/// variable `use_selection` is `True` when only the selected objects should be exported, which
+/// exporters should respect for options such as [`ConversionOptions::per_object`] to work.
+///
+/// [`ConversionOptions::incremental`] tells exporters apart by their [`Debug`](std::fmt::Debug)
+/// output, so it should include every setting that changes what the exporter writes. A derived
+/// implementation does.
+///
+/// # Example
+///
+/// ```
+/// use blend_converter::{ConversionOptions, Exporter};
+///
+/// #[derive(Debug)]
+/// struct Stl;
+///
+/// impl Exporter for Stl {
+///     fn extension(&self) -> &str {
+///         "stl"
+///     }
+///
+///     fn export_call(&self, filepath: &str, _options: &ConversionOptions) -> String {
+///         format!("bpy.ops.wm.stl_export(filepath={filepath}, export_selected_objects=use_selection)")
+///     }
+/// }
+///
+/// let options = ConversionOptions {
+///     exporter: Some(std::sync::Arc::new(Stl)),
+///     ..Default::default()
+/// };
+/// ```
+pub trait Exporter: std::fmt::Debug + Send + Sync {
+    /// The file extension, without the leading `.`, of the main file written
+    fn extension(&self) -> &str;
+
+    /// A python statement that exports to `filepath`, a python expression evaluating to the path
+    /// to write to. `options` are the options the conversion is using.
+    fn export_call(&self, filepath: &str, options: &ConversionOptions) -> String;
+
+    /// The path the exporter writes its main file to when converting to `output`. By default
+    /// [`Exporter::extension`] is appended unless `output` already has it.
+    fn exported_path(&self, output: &Path) -> PathBuf {
+        if output
+            .extension()
+            .is_some_and(|ext| ext == self.extension())
+        {
+            output.to_path_buf()
+        } else {
+            append_extension(output, self.extension())
+        }
+    }
+}
+
+/// Custom exporters are only equal to themselves, so options sharing one through a clone are equal
+impl PartialEq for dyn Exporter {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(self, other)
+    }
+}
+
+fn append_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+/// The output file format to export to
+///
+/// The default format is [`OutputFormat::Glb`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum OutputFormat {
+    /// glTF Binary (.glb) Exports a single file, with all data packed in binary form
+    #[default]
+    Glb,
+    /// glTF Embedded (.gltf) Exports a single file, with all data packed in JSON
+    GltfEmbedded,
+    /// glTF Separate (.gltf + .bin + textures) Exports multiple files, with separate JSON, binary
+    /// and texture data
+    GltfSeparate,
+    /// Autodesk FBX (.fbx) Exports a single file. Textures are referenced by path rather than
+    /// embedded.
+    Fbx,
+    /// Wavefront OBJ (.obj + .mtl) Exports meshes and curves as geometry with materials in a
+    /// separate .mtl file, other objects such as armatures, cameras and lights are not exported
+    Obj,
+    /// STL (.stl) Exports geometry only as binary triangles, without materials or UVs. Requires
+    /// blender 4.2 or newer.
+    Stl,
+    /// Stanford PLY (.ply) Exports geometry with vertex normals, UVs and colors. Requires blender
+    /// 3.6 or newer.
+    Ply,
+    /// Universal Scene Description (.usd, .usdc or .usda) Exports a single file, binary unless
+    /// the output ends in .usda. Textures are referenced by path. The up axis is left as blender's
+    /// +Z whatever [`ConversionOptions::yup`] is set to.
+    Usd,
+    /// Universal Scene Description zip archive (.usdz) Exports a single file with textures
+    /// packed in, as used by AR viewers
+    Usdz,
+    /// Alembic (.abc) Exports geometry and its animation baked as a cache, without materials. The
+    /// up axis is always +Y.
+    Alembic,
+}
+
+impl OutputFormat {
+    /// The format matching the extension of `output`, or `self` if the extension is not
+    /// recognized. A `.gltf` output keeps [`OutputFormat::GltfSeparate`] and is otherwise
+    /// [`OutputFormat::GltfEmbedded`]. `.usd`, `.usdc` and `.usda` are all [`OutputFormat::Usd`].
+    pub fn for_output(self, output: &Path) -> Self {
+        let Some(extension) = output.extension() else {
+            return self;
+        };
+        let is = |candidate: &str| extension.eq_ignore_ascii_case(candidate);
+        if is("glb") {
+            Self::Glb
+        } else if is("gltf") && self != Self::GltfSeparate {
+            Self::GltfEmbedded
+        } else if is("fbx") {
+            Self::Fbx
+        } else if is("obj") {
+            Self::Obj
+        } else if is("stl") {
+            Self::Stl
+        } else if is("ply") {
+            Self::Ply
+        } else if is("usd") || is("usdc") || is("usda") {
+            Self::Usd
+        } else if is("usdz") {
+            Self::Usdz
+        } else if is("abc") {
+            Self::Alembic
+        } else {
+            self
+        }
+    }
+
+    /// Whether the format is one of the glTF variants
+    pub fn is_gltf(&self) -> bool {
+        matches!(self, Self::Glb | Self::GltfEmbedded | Self::GltfSeparate)
+    }
+
+    /// The oldest `(major, minor, patch)` blender version with the exporter for this format, if
+    /// it is newer than the versions supported by every format
+    pub fn min_blender_version(&self) -> Option<(u32, u32, u32)> {
+        match self {
+            Self::Obj => Some((3, 1, 0)),
+            Self::Ply => Some((3, 6, 0)),
+            Self::Stl => Some((4, 2, 0)),
+            _ => None,
+        }
+    }
+
+    fn gltf_call(format: &str, filepath: &str, options: &ConversionOptions) -> String {
+        let mut kwargs = PyKwargs::default();
+        kwargs.set("filepath", filepath);
+        kwargs.set("export_format", format!("{format:?}"));
+        // Blender 4.2 hid the embedded format behind a preference of the exporter add-on
+        let enable_embedded = if format == "GLTF_EMBEDDED" {
+            "gltf_addon = bpy.context.preferences.addons.get('io_scene_gltf2')
+if gltf_addon and hasattr(gltf_addon.preferences, 'allow_embedded_format'):
+    gltf_addon.preferences.allow_embedded_format = True
+"
+        } else {
+            ""
+        };
+        kwargs.set("check_existing", format_py_bool(options.check_existing));
+        kwargs.set("export_apply", format_py_bool(options.apply_modifiers));
+        kwargs.set("export_extras", format_py_bool(options.extras));
+        kwargs.set("export_yup", format_py_bool(options.yup));
+        kwargs.set("use_selection", "use_selection");
+        // Otherwise every scene is exported with the active one as the default
+        if options.export_scenes == SceneSelection::All || options.scene.is_some() {
+            kwargs.set("use_active_scene", "True");
+        }
+        options.gltf.set_kwargs(&mut kwargs);
+        if options.active_collection_only {
+            kwargs.set("use_active_collection", "True");
+            kwargs.set("use_active_collection_with_nested", "True");
+        }
+        let animation_mode = options.animation_mode();
+        if animation_mode != AnimationMode::Actions {
+            kwargs.set(
+                "export_animation_mode",
+                format_py_str(animation_mode.gltf_name()),
+            );
+        }
+        if let Some(step) = options.animation.frame_step {
+            kwargs.set("export_force_sampling", "True");
+            kwargs.set("export_frame_step", step.max(1).to_string());
+        }
+        if let Some(quality) = options.image_quality {
+            kwargs.set("export_jpeg_quality", quality.min(100).to_string());
+        }
+        options.profile.set_kwargs(&mut kwargs);
+        format!("{enable_embedded}bpy.ops.export_scene.gltf({kwargs})")
+    }
+
+    fn fbx_call(filepath: &str, options: &ConversionOptions) -> String {
+        let mut kwargs = PyKwargs::default();
+        kwargs.set("filepath", filepath);
+        kwargs.set("check_existing", format_py_bool(options.check_existing));
+        kwargs.set("use_selection", "use_selection");
+        kwargs.set(
+            "use_active_collection",
+            format_py_bool(options.active_collection_only),
+        );
+        kwargs.set(
+            "use_mesh_modifiers",
+            format_py_bool(options.apply_modifiers),
+        );
+        kwargs.set("use_custom_props", format_py_bool(options.extras));
+        // By default FBX exports every action and every NLA strip
+        let animation_mode = options.animation_mode();
+        if animation_mode != AnimationMode::Actions {
+            kwargs.set("bake_anim_use_all_actions", "False");
+            kwargs.set(
+                "bake_anim_use_nla_strips",
+                format_py_bool(animation_mode == AnimationMode::NlaTracks),
+            );
+        }
+        if let Some(step) = options.animation.frame_step {
+            kwargs.set("bake_anim_step", step.max(1).to_string());
+        }
+        if !options.yup {
+            kwargs.set("axis_forward", format_py_str("Y"));
+            kwargs.set("axis_up", format_py_str("Z"));
+        }
+        format!("bpy.ops.export_scene.fbx({kwargs})")
+    }
+
+    fn obj_call(filepath: &str, options: &ConversionOptions) -> String {
+        let mut kwargs = PyKwargs::default();
+        kwargs.set("filepath", filepath);
+        kwargs.set("check_existing", format_py_bool(options.check_existing));
+        kwargs.set("export_selected_objects", "use_selection");
+        kwargs.set("apply_modifiers", format_py_bool(options.apply_modifiers));
+        if !options.yup {
+            kwargs.set("forward_axis", format_py_str("Y"));
+            kwargs.set("up_axis", format_py_str("Z"));
+        }
+        format!("bpy.ops.wm.obj_export({kwargs})")
+    }
+
+    /// The STL and PLY exporters share their options
+    fn mesh_call(operator: &str, filepath: &str, options: &ConversionOptions) -> String {
+        let mut kwargs = PyKwargs::default();
+        kwargs.set("filepath", filepath);
+        kwargs.set("check_existing", format_py_bool(options.check_existing));
+        kwargs.set("export_selected_objects", "use_selection");
+        kwargs.set("apply_modifiers", format_py_bool(options.apply_modifiers));
+        if options.yup {
+            kwargs.set("forward_axis", format_py_str("NEGATIVE_Z"));
+            kwargs.set("up_axis", format_py_str("Y"));
+        }
+        format!("bpy.ops.wm.{operator}({kwargs})")
+    }
+
+    fn usd_call(filepath: &str, options: &ConversionOptions) -> String {
+        let mut kwargs = PyKwargs::default();
+        kwargs.set("filepath", filepath);
+        kwargs.set("check_existing", format_py_bool(options.check_existing));
+        kwargs.set("selected_objects_only", "use_selection");
+        format!("bpy.ops.wm.usd_export({kwargs})")
+    }
+
+    fn alembic_call(filepath: &str, options: &ConversionOptions) -> String {
+        let mut kwargs = PyKwargs::default();
+        kwargs.set("filepath", filepath);
+        kwargs.set("check_existing", format_py_bool(options.check_existing));
+        kwargs.set("selected", "use_selection");
+        format!("bpy.ops.wm.alembic_export({kwargs})")
+    }
+}
+
+impl Exporter for OutputFormat {
+    fn extension(&self) -> &str {
+        match self {
+            Self::Glb => "glb",
+            Self::GltfEmbedded | Self::GltfSeparate => "gltf",
+            Self::Fbx => "fbx",
+            Self::Obj => "obj",
+            Self::Stl => "stl",
+            Self::Ply => "ply",
+            Self::Usd => "usdc",
+            Self::Usdz => "usdz",
+            Self::Alembic => "abc",
+        }
+    }
+
+    fn export_call(&self, filepath: &str, options: &ConversionOptions) -> String {
+        match self {
+            Self::Glb => Self::gltf_call("GLB", filepath, options),
+            Self::GltfEmbedded => Self::gltf_call("GLTF_EMBEDDED", filepath, options),
+            Self::GltfSeparate => Self::gltf_call("GLTF_SEPARATE", filepath, options),
+            Self::Fbx => Self::fbx_call(filepath, options),
+            Self::Obj => Self::obj_call(filepath, options),
+            Self::Stl => Self::mesh_call("stl_export", filepath, options),
+            Self::Ply => Self::mesh_call("ply_export", filepath, options),
+            Self::Usd | Self::Usdz => Self::usd_call(filepath, options),
+            Self::Alembic => Self::alembic_call(filepath, options),
+        }
+    }
+
+    /// Like blender we replace a .glb or .gltf extension with the right one for glTF and append
+    /// it otherwise. The other exporters write to the path as given so their extension is
+    /// appended unless already present, for USD any of its extensions.
+    fn exported_path(&self, output: &Path) -> PathBuf {
+        let has_extension = |candidates: &[&str]| {
+            output.extension().is_some_and(|ext| {
+                candidates
+                    .iter()
+                    .any(|candidate| ext.eq_ignore_ascii_case(candidate))
+            })
+        };
+        if self.is_gltf() && has_extension(&["glb", "gltf"]) {
+            output.with_extension(self.extension())
+        } else if has_extension(&[self.extension()])
+            || (*self == Self::Usd && has_extension(&["usd", "usda"]))
+        {
+            output.to_path_buf()
+        } else {
+            append_extension(output, self.extension())
+        }
+    }
+}
+
+/// Settings only used by the glTF [`OutputFormat`]s, see [`ConversionOptions::gltf`]
+///
+/// Applying modifiers and the up axis are set with [`ConversionOptions::apply_modifiers`] and
+/// [`ConversionOptions::yup`] as they apply to every format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct GltfExportSettings {
+    /// Export cameras. Defaults to false.
+    pub export_cameras: bool,
+    /// Export lights as `KHR_lights_punctual`, [`Profile::Baseline`] always leaves them out.
+    /// Defaults to false.
+    pub export_lights: bool,
+    /// Only export the objects selected when the blend was saved. Defaults to false.
+    pub use_selection: bool,
+    /// How materials are exported. Defaults to [`GltfMaterials::Export`].
+    pub export_materials: GltfMaterials,
+    /// Export animations. Defaults to true.
+    pub export_animations: bool,
+    /// Export vertex tangents with the normals. Defaults to false.
+    pub export_tangents: bool,
+    /// Compress meshes with `KHR_draco_mesh_compression` at this level from 0, fastest, to 6,
+    /// smallest. Higher levels are clamped to 6 and [`Profile::Baseline`] never compresses.
+    /// Defaults to None.
+    pub draco_compression: Option<u8>,
+    /// The format images are written in. Defaults to [`GltfImageFormat::Auto`].
+    pub image_format: GltfImageFormat,
+    /// Directory the images of [`OutputFormat::GltfSeparate`] are written to, relative to the
+    /// .gltf file, e.g. `textures`. The image URIs point into it. Defaults to None which writes
+    /// images next to the .gltf file.
+    pub texture_dir: Option<PathBuf>,
+}
+
+impl Default for GltfExportSettings {
+    fn default() -> Self {
+        Self {
+            export_cameras: false,
+            export_lights: false,
+            use_selection: false,
+            export_materials: GltfMaterials::default(),
+            export_animations: true,
+            export_tangents: false,
+            draco_compression: None,
+            image_format: GltfImageFormat::default(),
+            texture_dir: None,
+        }
+    }
+}
+
+impl GltfExportSettings {
+    fn set_kwargs(&self, kwargs: &mut PyKwargs) {
+        kwargs.set("export_cameras", format_py_bool(self.export_cameras));
+        kwargs.set("export_lights", format_py_bool(self.export_lights));
+        kwargs.set(
+            "export_materials",
+            format_py_str(self.export_materials.py_name()),
+        );
+        kwargs.set("export_animations", format_py_bool(self.export_animations));
+        kwargs.set("export_tangents", format_py_bool(self.export_tangents));
+        if let Some(level) = self.draco_compression {
+            kwargs.set("export_draco_mesh_compression_enable", "True");
+            kwargs.set(
+                "export_draco_mesh_compression_level",
+                level.min(6).to_string(),
+            );
+        }
+        kwargs.set(
+            "export_image_format",
+            format_py_str(self.image_format.py_name()),
+        );
+        if let Some(dir) = &self.texture_dir {
+            kwargs.set("export_texture_dir", format_py_path(dir));
+        }
+    }
+}
+
+/// The format glTF images are written in, see [`GltfExportSettings::image_format`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum GltfImageFormat {
+    /// PNG, or JPEG for images that are JPEGs in the blend
+    #[default]
+    Auto,
+    /// JPEG for every image, with the quality set by [`ConversionOptions::image_quality`]
+    Jpeg,
+    /// WebP for every image. Requires blender 4.0 or newer.
+    Webp,
+    /// Leave images out of the export, materials still reference their textures by name
+    None,
+    /// KTX2 with Basis Universal compression, referenced through `KHR_texture_basisu`. Images
+    /// are exported like [`GltfImageFormat::Auto`] then encoded with KTX-Software's `toktx`,
+    /// which must be in the path, and the originals removed. Only the image files of
+    /// [`OutputFormat::GltfSeparate`] are encoded, images packed into other formats are kept as
+    /// they are.
+    Ktx2,
+}
+
+impl GltfImageFormat {
+    fn py_name(self) -> &'static str {
+        match self {
+            Self::Auto | Self::Ktx2 => "AUTO",
+            Self::Jpeg => "JPEG",
+            Self::Webp => "WEBP",
+            Self::None => "NONE",
+        }
+    }
+}
+
+/// How materials are written to glTF, see [`GltfExportSettings::export_materials`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum GltfMaterials {
+    /// Export materials and their textures
+    #[default]
+    Export,
+    /// Keep material slots but write no material data, so primitives stay split by material
+    Placeholder,
+    /// Export no materials and merge the primitives of every slot
+    None,
+}
+
+impl GltfMaterials {
+    fn py_name(self) -> &'static str {
+        match self {
+            Self::Export => "EXPORT",
+            Self::Placeholder => "PLACEHOLDER",
+            Self::None => "NONE",
+        }
+    }
+}
+
+/// A compatibility profile restricting which glTF extensions the export may use
+///
+/// The default profile is [`Profile::Full`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Profile {
+    /// Leave blender free to emit any extension it supports
+    #[default]
+    Full,
+    /// Conservative settings for picky or older glTF runtimes. Disables draco mesh compression
+    /// (`KHR_draco_mesh_compression`) and punctual lights (`KHR_lights_punctual`), and clamps
+    /// principled BSDF emission strength to 1.0 so `KHR_materials_emissive_strength` is not
+    /// emitted.
+    ///
+    /// Other material extensions (e.g. `KHR_texture_transform`, `KHR_materials_transmission`)
+    /// are only emitted by blender when the material uses the matching feature and have no export
+    /// toggle, so avoid those features in the blend.
+    Baseline,
+}
+
+impl Profile {
+    /// Python run before exporting to prepare the scene for the profile
+    pub(crate) fn script(&self) -> &'static str {
+        match self {
+            Self::Full => "",
+            Self::Baseline => {
+                "for m in bpy.data.materials:
+    for n in (m.node_tree.nodes if m.node_tree else []):
+        s = n.inputs.get('Emission Strength') if n.type == 'BSDF_PRINCIPLED' else None
+        if s is not None and s.default_value > 1.0:
+            s.default_value = 1.0
+"
+            }
+        }
+    }
+
+    fn set_kwargs(&self, kwargs: &mut PyKwargs) {
+        match self {
+            Self::Full => {}
+            Self::Baseline => {
+                kwargs.set("export_draco_mesh_compression_enable", "False");
+                kwargs.set("export_lights", "False");
+            }
+        }
+    }
+}
@@ -1,13 +1,14 @@
 #![warn(clippy::unwrap_used, missing_docs)]
 
 //! Blend Converter provides a convenient way to automatically convert blender files (.blend) to
-//! other 3D file formats that are easier to work with. Currently the only formats specified are
-//! gltf based (see [`OutputFormat`]).
+//! other 3D file formats that are easier to work with, glTF by default and any of blender's other
+//! built in exporters (see [`OutputFormat`]).
 //!
 //! # Blender Executable
-//! To convert blends we need a blender executable. By default we check the path for `blender` and
-//! flatpak but if you need to specify a path use [`ConversionOptions::blender_path`]. For more
-//! details about the search strategy see [`BlenderExecutable`].
+//! To convert blends we need a blender executable. By default we check the path for `blender`,
+//! the standard install locations on Windows and flatpak but if you need to specify a path use
+//! [`ConversionOptions::blender_path`]. For more details about the search strategy see
+//! [`BlenderExecutable`].
 //!
 //! # Example
 //!
@@ -44,21 +45,66 @@
 //! ```
 //!
 
+use std::collections::HashMap;
 use std::env;
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
+use std::io::{BufRead, BufReader, Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::{Command, ExitStatus};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 use walkdir::WalkDir;
 
+mod builder;
+mod cache;
+mod cancel;
+#[cfg(feature = "config")]
+mod config;
+mod format;
+#[cfg(feature = "serde")]
+mod manifest;
+mod postprocess;
+mod python;
+mod session;
+#[cfg(feature = "watch")]
+mod watch;
+
+pub use builder::ConversionOptionsBuilder;
+use cache::{content_hash, Cache};
+pub use cancel::CancellationToken;
+#[cfg(feature = "config")]
+pub use config::Config;
+pub use format::{
+    Exporter, GltfExportSettings, GltfImageFormat, GltfMaterials, OutputFormat, Profile,
+};
+#[cfg(feature = "serde")]
+pub use manifest::{AnimationInfo, BlendManifest, CollectionInfo, ObjectInfo};
+use python::{format_py_bool, format_py_path, format_py_str, format_py_str_list, indent};
+pub use session::{BlenderPool, BlenderSession};
+
 /// ConversionOptions describe how blender files should be converted
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
 pub struct ConversionOptions {
     /// Ouput format is the desired file format to convert to
     pub output_format: OutputFormat,
+    /// A custom exporter to use instead of [`ConversionOptions::output_format`], see [`Exporter`].
+    /// Options specific to glTF have no effect unless the exporter reads them. Defaults to None.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub exporter: Option<Arc<dyn Exporter>>,
     /// Blender path is an optional override path for where to search for blender. If it is None
     /// [`BlenderExecutable::find`] will be used. Read the documentation there for the search
     /// strategy.
     pub blender_path: Option<PathBuf>,
+    /// Run this blender instead of searching for one, such as a
+    /// [`BlenderExecutable::Docker`] image. Takes precedence over
+    /// [`ConversionOptions::blender_path`]. Defaults to None.
+    pub blender_executable: Option<BlenderExecutable>,
     /// Checks if the export path file already exists and does not overwrite it.
     pub check_existing: bool,
     /// Apply modifiers exclusing Armatures to mesh objects. Prevents exporting shape keys. Defaults to false.
@@ -67,264 +113,5471 @@ pub struct ConversionOptions {
     pub extras: bool,
     /// Export using +Y as up instead of blender's +Z. Defaults to true.
     pub yup: bool,
+    /// Compatibility profile restricting the glTF extensions used. Defaults to [`Profile::Full`].
+    pub profile: Profile,
+    /// Which objects and data the glTF formats export, see [`GltfExportSettings`]. Defaults to
+    /// blender's own defaults.
+    pub gltf: GltfExportSettings,
+    /// Export into a temporary directory next to the output and only move the files into place
+    /// once blender succeeds, so an interrupted conversion never leaves a truncated output behind.
+    /// Defaults to false.
+    pub atomic: bool,
+    /// Convert every blend in [`ConversionOptions::convert_dir`] in a single blender process
+    /// instead of starting blender for each file, which saves blender's startup time per file.
+    /// Conversion stops at the first blend that fails like it does without batching. Batch
+    /// conversions are not atomic even if [`ConversionOptions::atomic`] is set. Defaults to false.
+    pub batch: bool,
+    /// Convert up to this many blends in [`ConversionOptions::convert_dir`] at once, each in its
+    /// own blender process. Every blend is then exported as if [`ConversionOptions::atomic`] were
+    /// set. When a blend fails no more are started and the first error is returned once the
+    /// running conversions finish, see [`ConversionOptions::convert_dir_report`] to convert the
+    /// rest anyway. Has no effect with [`ConversionOptions::batch`]. Defaults to
+    /// None which converts one blend at a time.
+    pub jobs: Option<usize>,
+    /// Reconvert every blend in [`ConversionOptions::convert_dir`]. Otherwise blends are skipped
+    /// when their output exists and was modified after the blend, and for
+    /// [`OutputFormat::GltfSeparate`] the .bin next to it exists too. Only the blend's own
+    /// modification time is checked, so use this after changing the options or linked libraries,
+    /// or use [`ConversionOptions::incremental`] which hashes the libraries too.
+    /// Defaults to false.
+    pub force: bool,
+    /// Skip blends in [`ConversionOptions::convert_dir`] whose contents and options are unchanged
+    /// since they were last converted, instead of comparing modification times. Hashes of each
+    /// blend and of the options are kept in a `.blend_converter_cache.json` manifest in the output
+    /// directory, along with hashes of the libraries each blend links so editing a library
+    /// converts the blends using it again. Blends converted by a run that fails are converted
+    /// again next time. Options that do not change the output, such as
+    /// [`ConversionOptions::timeout`], are not part of the hash. A custom
+    /// [`ConversionOptions::exporter`] is hashed through its [`Debug`](std::fmt::Debug) output,
+    /// so it must include every setting that changes what it writes. Defaults to false.
+    pub incremental: bool,
+    /// Where [`ConversionOptions::convert_dir`] writes each blend's export. Defaults to
+    /// [`OutputLayout::PreserveRelative`].
+    pub output_layout: OutputLayout,
+    /// What to do when a conversion would replace an existing export. Blends skipped by
+    /// [`ConversionOptions::convert_dir`] for being up to date are not affected. Defaults to
+    /// [`OverwritePolicy::Overwrite`].
+    pub overwrite: OverwritePolicy,
+    /// Clear the scene world (environment / HDRI) before exporting. glTF does not carry the world
+    /// anyway but other formats may. Defaults to false.
+    pub strip_world: bool,
+    /// Force every material to export as unlit using the glTF `KHR_materials_unlit` extension.
+    /// Defaults to false.
+    ///
+    /// Materials whose surface is a Principled BSDF are rewired so that the BSDF's base color (or
+    /// the texture linked into it) drives a Background shader, which blender's glTF exporter
+    /// recognises as unlit. Other inputs such as alpha and normal maps are dropped. Materials
+    /// using any other surface shader are left unchanged. Only applies to glTF formats.
+    pub unlit: bool,
+    /// JPEG compression quality from 0 to 100 for textures exported as JPEG, higher values are
+    /// larger files. If it is None blender's default of 75 is used. Values above 100 are treated
+    /// as 100.
+    pub image_quality: Option<u8>,
+    /// Rewrite the exported glTF so nodes and meshes are sorted by name and references to them
+    /// are remapped. This makes the output stable across unrelated edits to the blend which keeps
+    /// diffs of the output meaningful. Only applies to the glTF [`OutputFormat`]s. Defaults to
+    /// false.
+    pub deterministic_ordering: bool,
+    /// Generate levels of detail for every exported mesh object, embedded in the same file with
+    /// the `MSFT_lod` glTF extension. Level `n` is a copy of the object decimated with blender's
+    /// Decimate (collapse) modifier to `lod_ratios[n - 1]` of the original face count, so
+    /// `vec![0.5, 0.25]` adds `<object>.LOD1` with half and `<object>.LOD2` with a quarter of the
+    /// faces. Ratios are clamped to between 0 and 1. The extension is only added for the glTF
+    /// [`OutputFormat`]s, other formats export the levels as ordinary objects. Defaults to
+    /// empty.
+    pub lod_ratios: Vec<f32>,
+    /// Wrap the root nodes of every scene in the exported glTF in a new node with this transform,
+    /// e.g. to scale models for an engine using different units. The blend is not changed, the
+    /// node is added to the output afterwards. Only applies to the glTF [`OutputFormat`]s.
+    /// Defaults to None.
+    pub root_transform: Option<RootTransform>,
+    /// Objects with names matching this pattern are exported to their own `<stem>.collision`
+    /// file and excluded from the main export, following the Unreal/Unity collision mesh
+    /// convention e.g. `UCX_*`. The pattern uses python's `fnmatch` syntax (`*`, `?` and `[seq]`)
+    /// and is case sensitive. Collision objects are exported even if other options would exclude
+    /// them e.g. [`ConversionOptions::respect_hide_render`]. Defaults to None.
+    pub collision_pattern: Option<String>,
+    /// Write the cameras and lights in the blend to a `<stem>.scene.json` file next to the
+    /// export, for runtimes that set up their scenes themselves. Positions and rotations are in
+    /// blender's Z up world space whatever [`ConversionOptions::yup`] is set to, rotations are
+    /// `[w, x, y, z]` quaternions and angles are in radians:
+    ///
+    /// ```json
+    /// {
+    ///   "cameras": [{ "name": "Camera", "position": [0, 0, 0], "rotation": [1, 0, 0, 0],
+    ///     "type": "PERSP", "fov": 0.69, "ortho_scale": 6, "clip_start": 0.1, "clip_end": 100 }],
+    ///   "lights": [{ "name": "Light", "position": [0, 0, 0], "rotation": [1, 0, 0, 0],
+    ///     "type": "SPOT", "color": [1, 1, 1], "energy": 1000, "spot_size": 0.78 }]
+    /// }
+    /// ```
+    ///
+    /// `fov` is along the camera's sensor fit axis and `spot_size` is only present for spot
+    /// lights. Defaults to false.
+    pub scene_sidecar: bool,
+    /// Also write a `<name>.meta.json` manifest next to the export describing what the blend
+    /// contains, for reading at build time with [`BlendManifest::load`] from the `serde` feature. It lists the scene's
+    /// objects, its collection hierarchy and every action:
+    ///
+    /// ```json
+    /// {
+    ///   "objects": [{ "name": "Rig", "type": "ARMATURE", "parent": null,
+    ///     "collections": ["Characters"], "materials": [], "properties": { "speed": 2.5 } }],
+    ///   "collections": [{ "name": "Characters", "parent": null, "properties": {} }],
+    ///   "animations": [{ "name": "Walk", "frame_start": 1, "frame_end": 24, "objects": ["Rig"] }]
+    /// }
+    /// ```
+    ///
+    /// Objects directly in the scene's root collection list it as `Scene Collection`. An
+    /// animation's `objects` are those using it as their action or in an NLA strip. The manifest
+    /// describes the blend before any filtering, so it includes objects left out of the export.
+    /// Defaults to false.
+    pub manifest: bool,
+    /// Leave cameras and lights out of the export, usually combined with
+    /// [`ConversionOptions::scene_sidecar`]. Defaults to false.
+    pub exclude_cameras_and_lights: bool,
+    /// Seed for the random number generators used inside blender so repeated conversions produce
+    /// identical output. This seeds python's `random` module (used by pre export scripts) and sets
+    /// the Cycles sampling seed of every scene (used when baking or rendering) with animated seeds
+    /// disabled. Blender's exporters and modifiers are otherwise deterministic. Defaults to None.
+    pub seed: Option<u64>,
+    /// Only export objects that are enabled for rendering, skipping objects with `hide_render`
+    /// set such as proxies and rigging helpers. This is independent of viewport visibility.
+    /// Defaults to false.
+    pub respect_hide_render: bool,
+    /// Only export objects visible in the view layer, skipping objects hidden in the viewport and
+    /// objects in hidden or excluded collections. Defaults to false.
+    pub visible_only: bool,
+    /// Only export objects in one of the collections with these names or the collections nested
+    /// in them. Defaults to empty which does not filter by collection.
+    pub include_collections: Vec<String>,
+    /// Leave out objects whose names match any of these patterns, e.g. `WGT-*` for rig widgets.
+    /// The patterns use the same syntax as [`ConversionOptions::collision_pattern`]. Defaults to
+    /// empty.
+    pub exclude_name_patterns: Vec<String>,
+    /// Only export objects whose custom property with this name equals the value, e.g.
+    /// `("export".into(), PropertyValue::Bool(true))`. The conversion fails with
+    /// [`Error::NothingToExport`] if no object matches. Defaults to None.
+    pub required_property: Option<(String, PropertyValue)>,
+    /// Rename materials before exporting, mapping the material name in the blend to the name it
+    /// should have in the output. Materials not in the map keep their name. If a new name is
+    /// already used by a material that is not being renamed blender will add a numeric suffix
+    /// such as `.001` to keep names unique.
+    pub material_remap: HashMap<String, String>,
+    /// Triangulate every mesh before exporting for runtimes that cannot handle quads or n-gons.
+    /// The glTF exporter already triangulates on export so this mainly controls the result for
+    /// other formats. Defaults to false.
+    pub triangulate: bool,
+    /// Print a warning on blender's stderr for each mesh that contains n-gons (faces with more
+    /// than four vertices) and report them in [`ConvertedBlend::ngons`]. The check happens before
+    /// [`ConversionOptions::triangulate`]. Defaults to false.
+    pub warn_on_ngons: bool,
+    /// Reverse the winding of every face, flipping their normals, of the meshes of the exported
+    /// objects for meshes that render inside out. Objects left out by the selection options
+    /// keep their meshes unless they share mesh data with an exported object. Unlike blender's
+    /// Recalculate Outside this flips every face unconditionally, so it only fixes meshes that
+    /// are consistently inside out and breaks meshes that are already correct. Defaults to false.
+    pub flip_winding: bool,
+    /// Export one animation per NLA track, named after the track, instead of one per action.
+    /// Tracks with the same name on different objects are combined into a single animation.
+    /// Requires blender 3.6 or newer. Same as [`AnimationMode::NlaTracks`] and takes precedence
+    /// over [`AnimationOptions::mode`]. Defaults to false.
+    pub nla_track_animations: bool,
+    /// Which animations are exported and how they are sampled. Defaults to exporting every action
+    /// as it is keyed.
+    pub animation: AnimationOptions,
+    /// Directory blender uses for user config, scripts and datafiles instead of the user's shared
+    /// blender config, by setting `BLENDER_USER_RESOURCES` and the older per kind variables. Useful
+    /// when the shared config is read only or to isolate conversions. The directory is created if
+    /// it does not exist. Defaults to None which leaves blender's default.
+    pub user_resources_dir: Option<PathBuf>,
+    /// Export each top level object, together with its children and any armature deforming it, to
+    /// its own `<stem>.<object>` file instead of exporting the whole scene to one file. Object
+    /// names are sanitized into valid file names and names that collide get a numeric suffix.
+    /// Objects sharing mesh data get a full copy of the data in each file. Defaults to false.
+    pub per_object: bool,
+    /// Export each collection directly under the scene collection, with the objects in its nested
+    /// collections, to its own `<stem>.<collection>` file instead of exporting the whole scene to
+    /// one file. Collection names are sanitized like object names for
+    /// [`ConversionOptions::per_object`], which takes precedence if both are set. Objects linked
+    /// only to the scene collection are not exported and collections with nothing to export are
+    /// skipped. Defaults to false.
+    pub per_collection: bool,
+    /// Which scenes of the blend are exported. [`SceneSelection::All`] writes each scene to its
+    /// own `<stem>__<scene>` file, with scene names sanitized like object names for
+    /// [`ConversionOptions::per_object`], which then splits each scene's objects into
+    /// `<stem>__<scene>.<object>` files. Steps that prepare the scene before exporting, such as
+    /// [`ConversionOptions::collision_pattern`] and [`ConversionOptions::lod_ratios`], only see
+    /// the active scene. Defaults to [`SceneSelection::Active`].
+    pub export_scenes: SceneSelection,
+    /// Export the scene with this name instead of the one that was active when the blend was
+    /// saved. Every step of the conversion sees this scene. The conversion fails with
+    /// [`Error::SceneNotFound`] if the blend has no scene with the name. Ignored with
+    /// [`SceneSelection::All`]. Defaults to None.
+    pub scene: Option<String>,
+    /// Replace the geometry of every mesh object with a simple proxy shape before exporting, e.g.
+    /// for streaming placeholders or distant versions of models. See [`ProxyKind`] for how each
+    /// proxy approximates the object. Defaults to None.
+    pub proxy_geometry: Option<ProxyKind>,
+    /// Add a UV map unwrapped with blender's Smart UV Project to every visible mesh, for baked
+    /// lightmaps. The new UV map is added after the existing ones so a mesh with one UV map gets
+    /// the lightmap as `TEXCOORD_1`. See [`LightmapUv`] for the unwrap parameters. Defaults to
+    /// None.
+    pub lightmap_uv: Option<LightmapUv>,
+    /// Render a PNG preview of the exported objects in the same blender run, written next to the
+    /// export as `<name>.thumbnail.png` and returned with the other written files. With
+    /// [`SceneSelection::All`] every scene gets its own thumbnail. Defaults to None.
+    pub render_thumbnail: Option<ThumbnailOptions>,
+    /// Only export the objects in the blend's active collection and its nested collections. This
+    /// follows whichever collection was active when the blend was saved. Defaults to false.
+    pub active_collection_only: bool,
+    /// With [`ConversionOptions::per_object`] or [`ConversionOptions::per_collection`], skip
+    /// objects or collections whose existing output is newer than the files their data comes
+    /// from. Blend files do not record when individual objects changed, so this is best effort:
+    /// objects linked from a library are compared with the library file and every other object
+    /// with the blend itself. A collection is compared with the files of all the objects in it,
+    /// so one local object makes it follow the blend. So editing a blend re-exports all of its
+    /// local objects and collections, and changing the conversion options does not count as a
+    /// change. Defaults to false.
+    pub skip_unchanged: bool,
+    /// Record the path of the source blend in the glTF `asset.extras` as `source_blend` so a
+    /// model can be traced back to where it came from. Only applies to the glTF
+    /// [`OutputFormat`]s. Defaults to false.
+    pub embed_source: bool,
+    /// Checks, measurements and optimizations applied to exported glTF files, see
+    /// [`PostProcess`]. Defaults to none of them.
+    pub post_process: PostProcess,
+    /// A version or commit identifier recorded next to the source blend as `source_version` when
+    /// [`ConversionOptions::embed_source`] is set. Defaults to None.
+    pub source_version: Option<String>,
+    /// Directories searched recursively, with blender's Find Missing Files, for images whose
+    /// file does not exist at the path stored in the blend. Images that are still missing
+    /// afterwards are reported as warnings on blender's stderr. Defaults to empty.
+    pub texture_search_dirs: Vec<PathBuf>,
+    /// Fail the conversion with [`Error::MissingTextures`] instead of warning when an image file
+    /// can not be found. Packed images and UDIM tiles are not checked. Defaults to false.
+    pub require_textures: bool,
+    /// Limit the address space of the blender process to this many bytes with `RLIMIT_AS`. A
+    /// blender that crashes after failing to allocate past the limit is reported as
+    /// [`Error::OutOfMemory`]. Address space includes memory that is reserved but never used, so
+    /// leave some headroom over the expected peak usage. Only applied on Linux. Defaults to None.
+    pub memory_limit: Option<u64>,
+    /// Connect blender's stdout to a pseudo terminal instead of a pipe, so blender prints what it
+    /// only prints to a terminal such as progress. Falls back to a pipe if a pseudo terminal can
+    /// not be allocated or on platforms other than unix. Defaults to false.
+    #[cfg(feature = "pty")]
+    pub pty: bool,
+    /// Kill blender and fail with [`Error::Timeout`] if it runs for longer than this. With
+    /// [`ConversionOptions::batch`] this is the limit for the one blender process converting the
+    /// whole directory. On unix blender is run in its own process group so it and anything it
+    /// started, including the flatpak sandbox, are killed together. In config files this is given
+    /// in seconds. Defaults to None.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_secs"))]
+    pub timeout: Option<Duration>,
+    /// Stops the conversion with [`Error::Cancelled`] when the token is cancelled from another
+    /// thread, killing blender. [`ConversionOptions::convert_dir`] starts no more blends and keeps
+    /// the ones already converted. Defaults to None.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub cancellation: Option<CancellationToken>,
+    /// Fail with [`Error::UnsupportedVersion`] before converting if blender is older than this
+    /// `(major, minor, patch)` version, see [`BlenderExecutable::version`]. Blender is also
+    /// checked against the version the output format and other options need, see
+    /// [`OutputFormat::min_blender_version`]. Defaults to None.
+    pub min_version: Option<(u32, u32, u32)>,
+    /// Python run in the same blender as the export before any other step of the conversion, to
+    /// clean up or adjust the scene. `bpy` is already imported. An exception raised by the script
+    /// fails the conversion with [`Error::PythonException`]. Defaults to None.
+    pub pre_export_script: Option<PythonScript>,
+    /// Python run in the same blender after everything has been exported, with `bpy` imported
+    /// and the paths of the exported files in the list `exported_paths`. The files have not been
+    /// post processed yet. Errors fail the conversion like
+    /// [`ConversionOptions::pre_export_script`]. Defaults to None.
+    pub post_export_script: Option<PythonScript>,
 }
 
 impl Default for ConversionOptions {
     fn default() -> Self {
         Self {
             output_format: OutputFormat::default(),
+            exporter: None,
             check_existing: false,
             blender_path: None,
+            blender_executable: None,
             apply_modifiers: false,
             extras: false,
             yup: true,
+            profile: Profile::default(),
+            gltf: GltfExportSettings::default(),
+            atomic: false,
+            batch: false,
+            jobs: None,
+            force: false,
+            incremental: false,
+            output_layout: OutputLayout::default(),
+            overwrite: OverwritePolicy::default(),
+            strip_world: false,
+            unlit: false,
+            image_quality: None,
+            deterministic_ordering: false,
+            lod_ratios: Vec::new(),
+            root_transform: None,
+            collision_pattern: None,
+            scene_sidecar: false,
+            manifest: false,
+            exclude_cameras_and_lights: false,
+            seed: None,
+            respect_hide_render: false,
+            visible_only: false,
+            include_collections: Vec::new(),
+            exclude_name_patterns: Vec::new(),
+            required_property: None,
+            material_remap: HashMap::new(),
+            triangulate: false,
+            warn_on_ngons: false,
+            flip_winding: false,
+            nla_track_animations: false,
+            animation: AnimationOptions::default(),
+            user_resources_dir: None,
+            per_object: false,
+            per_collection: false,
+            export_scenes: SceneSelection::default(),
+            scene: None,
+            proxy_geometry: None,
+            lightmap_uv: None,
+            render_thumbnail: None,
+            active_collection_only: false,
+            skip_unchanged: false,
+            embed_source: false,
+            post_process: PostProcess::default(),
+            source_version: None,
+            texture_search_dirs: Vec::new(),
+            require_textures: false,
+            memory_limit: None,
+            #[cfg(feature = "pty")]
+            pty: false,
+            timeout: None,
+            cancellation: None,
+            min_version: None,
+            pre_export_script: None,
+            post_export_script: None,
         }
     }
 }
 
-/// The output file format to export to
-///
-/// The default format is [`OutputFormat::Glb`]
-#[derive(Debug, Default)]
-pub enum OutputFormat {
-    /// glTF Binary (.glb) Exports a single file, with all data packed in binary form
-    #[default]
-    Glb,
-    /// glTF Embedded (.gltf) Exports a single file, with all data packed in JSON
-    GltfEmbedded,
-    /// glTF Separate (.gltf + .bin + textures) Exports multiple files, with separate JSON, binary
-    /// and texture data
-    GltfSeparate,
+#[cfg(feature = "serde")]
+fn deserialize_secs<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let secs: Option<f64> = serde::Deserialize::deserialize(deserializer)?;
+    secs.map(|secs| Duration::try_from_secs_f64(secs).map_err(serde::de::Error::custom))
+        .transpose()
 }
 
-impl ConversionOptions {
-    fn export_script(&self, file_path: &Path) -> String {
-        let format = match &self.output_format {
-            OutputFormat::Glb => "GLB",
-            OutputFormat::GltfEmbedded => "GLTF_EMBEDDED",
-            OutputFormat::GltfSeparate => "GLTF_SEPARATE",
-        };
-        let check_existing = format_py_bool(self.check_existing);
-        let apply_modifiers = format_py_bool(self.apply_modifiers);
-        let extras = format_py_bool(self.extras);
-        let yup = format_py_bool(self.yup);
-        format!(
-            "import bpy; bpy.ops.export_scene.gltf(
-                filepath={file_path:?},
-                export_format={format:?},
-                check_existing={check_existing},
-                export_apply={apply_modifiers},
-                export_extras={extras},
-                export_yup={yup},
-            )"
-        )
-    }
+/// A custom property value, see [`ConversionOptions::required_property`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(untagged))]
+pub enum PropertyValue {
+    /// A boolean property. Blender stores these as ints so this also matches 0 and 1.
+    Bool(bool),
+    /// An integer property
+    Int(i64),
+    /// A string property
+    String(String),
 }
 
-fn format_py_bool(val: bool) -> &'static str {
-    if val {
-        "True"
-    } else {
-        "False"
+impl PropertyValue {
+    fn to_py(&self) -> String {
+        match self {
+            Self::Bool(value) => format_py_bool(*value).to_owned(),
+            Self::Int(value) => value.to_string(),
+            Self::String(value) => format_py_str(value),
+        }
     }
 }
 
-impl ConversionOptions {
-    /// Create a new ConversionOptions with default output format of [`OutputFormat::Glb`].
-    ///
-    /// This is equivalent to [`ConversionOptions::default`]
-    pub fn new() -> Self {
-        Self::default()
+/// Python run inside blender, see [`ConversionOptions::pre_export_script`]. In config files
+/// this is either a string of python or a table with the `path` of a python file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(untagged))]
+pub enum PythonScript {
+    /// Python source code
+    Inline(String),
+    /// A python file, read by blender when the script runs. Relative paths are relative to the
+    /// working directory, or to the config file when loaded with `Config::load`.
+    File {
+        /// Path to the file
+        path: PathBuf,
+    },
+}
+
+impl PythonScript {
+    /// Python running the script with `globals`, naming it `name` in tracebacks if it is inline
+    fn exec_call(&self, name: &str, globals: &str) -> String {
+        match self {
+            Self::Inline(source) => format!(
+                "exec(compile({}, '<{name}>', 'exec'), {globals})\n",
+                format_py_str(source)
+            ),
+            Self::File { path } => format!(
+                "exec(compile(open({path}, encoding='utf-8').read(), {path}, 'exec'), {globals})\n",
+                path = format_py_path(path)
+            ),
+        }
     }
+}
 
-    /// Walks a directory and converts all the blend files while preserving the directory
-    /// structure.
-    pub fn convert_dir(&self, input_dir: &Path, output_dir: &Path) -> Result<(), Error> {
-        let blender_exe = BlenderExecutable::find_using_options(self)?;
-        for entry in WalkDir::new(input_dir)
-            .into_iter()
-            .filter_map(|entry| entry.ok())
-        {
-            if let Ok(m) = entry.metadata() {
-                // Ignore directories
-                if !m.is_file() {
-                    continue;
-                }
+impl From<String> for PythonScript {
+    fn from(source: String) -> Self {
+        Self::Inline(source)
+    }
+}
 
-                let input_path = entry.path();
+impl From<&str> for PythonScript {
+    fn from(source: &str) -> Self {
+        Self::Inline(source.to_owned())
+    }
+}
 
-                // Ignore everything except blend files
-                if input_path.extension() != Some(OsStr::new("blend")) {
-                    continue;
-                }
+/// A transform in glTF's conventions, see [`ConversionOptions::root_transform`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct RootTransform {
+    /// Scale along the x, y and z axes
+    pub scale: [f64; 3],
+    /// Rotation as a unit quaternion in `[x, y, z, w]` order
+    pub rotation: [f64; 4],
+}
 
-                let base;
-                if let Some(entry_parent) = input_path.parent() {
-                    base = entry_parent;
-                } else {
-                    base = Path::new(".");
-                }
-                let stem = input_path
-                    .file_stem()
-                    .ok_or(Error::InvalidInputFile(input_path.to_path_buf()))?;
-                let output_path = Path::new(&output_dir).join(base).join(stem);
-                std::fs::create_dir_all(output_path.parent().expect("walkdir must have parent"))?;
+impl RootTransform {
+    /// A uniform scale with no rotation
+    pub fn uniform_scale(scale: f64) -> Self {
+        Self {
+            scale: [scale; 3],
+            ..Default::default()
+        }
+    }
+}
 
-                self.convert_internal(input_path, &output_path, &blender_exe)?;
-            }
+impl Default for RootTransform {
+    fn default() -> Self {
+        Self {
+            scale: [1.0; 3],
+            rotation: [0.0, 0.0, 0.0, 1.0],
         }
-        Ok(())
     }
+}
 
-    /// Walks a directory converts all the blend files while preserving the directory structure but
-    /// outputs them to OUT_DIR.
-    ///
-    /// For use in build scripts only.
-    ///
-    /// # Example
-    ///
-    /// ```no_run
-    /// use std::path::Path;
-    ///
-    /// let input_dir = Path::new("blends");
-    /// blend_converter::ConversionOptions::default()
-    ///     .convert_dir_build_script(input_dir)
-    ///     .expect("failed to convert blends");
-    /// println!("cargo:rerun-if-changed={}", input_dir.display());
-    /// println!("cargo:rerun-if-changed=build.rs");
-    /// ```
-    pub fn convert_dir_build_script(&self, input_dir: &Path) -> Result<(), Error> {
-        let output_dir_env =
-            env::var("OUT_DIR").expect("OUT_DIR is not set, this must be called from build.rs");
-        let output_dir = Path::new(&output_dir_env);
-        self.convert_dir(input_dir, output_dir)
+/// Parameters for generating a lightmap UV map, see [`ConversionOptions::lightmap_uv`]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct LightmapUv {
+    /// Name of the UV map. An existing UV map with this name is unwrapped again instead of adding
+    /// another. Defaults to `Lightmap`.
+    pub name: String,
+    /// Smart UV Project's angle limit in degrees, faces meeting at a sharper angle than this are
+    /// split into separate islands. Defaults to 66, blender's default.
+    pub angle_limit: f32,
+    /// Smart UV Project's island margin, the space between islands as a fraction of the UV
+    /// space. Lightmaps need some margin so texels do not bleed across islands. Defaults to 0.02.
+    pub island_margin: f32,
+}
+
+impl Default for LightmapUv {
+    fn default() -> Self {
+        Self {
+            name: "Lightmap".to_owned(),
+            angle_limit: 66.0,
+            island_margin: 0.02,
+        }
     }
+}
 
-    /// Convert an individual blend file
-    pub fn convert(&self, input: &Path, output: &Path) -> Result<(), Error> {
-        let blender_exe = BlenderExecutable::find_using_options(self)?;
-        self.convert_internal(input, output, &blender_exe)
+impl LightmapUv {
+    fn script(&self) -> String {
+        format!(
+            "import math
+view_layer = bpy.context.view_layer
+# One object per mesh, shared meshes only need unwrapping once
+unwrap = list({{o.data: o for o in view_layer.objects if o.type == 'MESH' and o.data.polygons and o.visible_get()}}.values())
+if unwrap:
+    previous = []
+    for o in unwrap:
+        me = o.data
+        previous.append((me, me.uv_layers.active_index, len(me.uv_layers)))
+        me.uv_layers.active = me.uv_layers.get({name}) or me.uv_layers.new(name={name})
+    for o in view_layer.objects:
+        o.select_set(o in unwrap)
+    view_layer.objects.active = unwrap[0]
+    bpy.ops.object.mode_set(mode='EDIT')
+    bpy.ops.mesh.select_all(action='SELECT')
+    bpy.ops.uv.smart_project(angle_limit=math.radians({angle_limit:?}), island_margin={island_margin:?})
+    bpy.ops.object.mode_set(mode='OBJECT')
+    for me, index, count in previous:
+        if count:
+            me.uv_layers.active_index = index
+",
+            name = format_py_str(&self.name),
+            angle_limit = self.angle_limit,
+            island_margin = self.island_margin,
+        )
     }
+}
 
-    fn convert_internal(
-        &self,
-        input: &Path,
-        output: &Path,
-        blender_exe: &BlenderExecutable,
-    ) -> Result<(), Error> {
-        let input_file_path = input.canonicalize()?;
-        if input_file_path
-            .extension()
-            .ok_or(Error::InvalidInputFile(input_file_path.clone()))?
-            != "blend"
-        {
-            return Err(Error::InvalidInputFile(input_file_path));
-        }
+/// Controls for the exported animations, see [`ConversionOptions::animation`]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct AnimationOptions {
+    /// Which animations are exported. Defaults to [`AnimationMode::Actions`].
+    pub mode: AnimationMode,
+    /// Only keep the actions whose names match one of these `fnmatch` style patterns, such as
+    /// `Walk*`. Other actions are removed before exporting. Defaults to empty which keeps every
+    /// action.
+    pub include_actions: Vec<String>,
+    /// Remove the actions whose names match one of these patterns before exporting, even if they
+    /// match [`AnimationOptions::include_actions`]. Defaults to empty.
+    pub exclude_actions: Vec<String>,
+    /// Sample animations every this many frames instead of exporting their keyframes. Defaults to
+    /// None which leaves sampling to the exporter.
+    pub frame_step: Option<u32>,
+    /// Bake the motion constraints give visible objects and bones into keyframes over the
+    /// scene's frame range, then remove the constraints, so exporters that ignore constraints
+    /// still see it. Baking uses the object's current action, creating one if it has none.
+    /// Runs after the actions are filtered. Defaults to false.
+    pub bake_constraints: bool,
+}
 
-        let status = blender_exe
-            .cmd()
-            .arg("-b")
-            .arg(input_file_path)
-            .arg("--python-exit-code")
-            .arg("10")
-            .arg("--python-expr")
-            .arg(self.export_script(output))
-            .status()?;
+/// Which animations are exported, see [`AnimationOptions::mode`]. Modes other than
+/// [`AnimationMode::Actions`] require blender 3.6 or newer for glTF.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum AnimationMode {
+    /// Every action that can be applied to an object, each as its own animation
+    #[default]
+    Actions,
+    /// Only the action each object currently has assigned
+    ActiveActions,
+    /// One animation per NLA track, see [`ConversionOptions::nla_track_animations`]
+    NlaTracks,
+    /// The scene's timeline as a single animation. Exported like
+    /// [`AnimationMode::ActiveActions`] by FBX.
+    Scene,
+}
 
-        dbg!(status);
-        if status.success() {
-            Ok(())
-        } else {
-            Err(Error::Export(status))
+impl AnimationMode {
+    /// The glTF exporter's `export_animation_mode`
+    fn gltf_name(self) -> &'static str {
+        match self {
+            Self::Actions => "ACTIONS",
+            Self::ActiveActions => "ACTIVE_ACTIONS",
+            Self::NlaTracks => "NLA_TRACKS",
+            Self::Scene => "SCENE",
         }
     }
 }
 
-/// The blender executable search strategy
-#[derive(Debug, Default)]
-pub enum BlenderExecutable {
-    /// Invokes blender using `blender` because blender is in the path environment variable
-    #[default]
-    Normal,
-    /// Invokes blender using `flatpak run org.blender.Blender`
-    Flatpak,
-    /// Invokes blender using path provided by [`ConversionOptions::blender_path`]
-    Path(PathBuf),
+impl AnimationOptions {
+    /// Python removing filtered actions and baking constraints, empty when neither is needed
+    fn script(&self) -> String {
+        let mut script = String::new();
+        if !self.include_actions.is_empty() || !self.exclude_actions.is_empty() {
+            script.push_str(&format!(
+                "import fnmatch
+def action_matches(a, patterns):
+    return any(fnmatch.fnmatchcase(a.name, p) for p in patterns)
+include_actions, exclude_actions = {}, {}
+for a in list(bpy.data.actions):
+    if (include_actions and not action_matches(a, include_actions)) or action_matches(a, exclude_actions):
+        bpy.data.actions.remove(a)
+",
+                format_py_str_list(&self.include_actions),
+                format_py_str_list(&self.exclude_actions),
+            ));
+        }
+        if self.bake_constraints {
+            script.push_str(&format!(
+                "view_layer = bpy.context.view_layer
+scene = bpy.context.scene
+for o in [o for o in view_layer.objects if o.visible_get()]:
+    pose = o.pose is not None and any(b.constraints for b in o.pose.bones)
+    if not o.constraints and not pose:
+        continue
+    for other in view_layer.objects:
+        other.select_set(other == o)
+    view_layer.objects.active = o
+    if pose:
+        bpy.ops.object.mode_set(mode='POSE')
+        bpy.ops.pose.select_all(action='SELECT')
+    bpy.ops.nla.bake(frame_start=scene.frame_start, frame_end=scene.frame_end, step={step}, only_selected=True, visual_keying=True, clear_constraints=True, use_current_action=True, bake_types={{'POSE', 'OBJECT'}} if pose else {{'OBJECT'}})
+    if pose:
+        bpy.ops.object.mode_set(mode='OBJECT')
+",
+                step = self.frame_step.unwrap_or(1).max(1),
+            ));
+        }
+        script
+    }
 }
 
-impl BlenderExecutable {
-    fn find_using_options(options: &ConversionOptions) -> Result<Self, Error> {
-        if let Some(path) = &options.blender_path {
-            BlenderExecutable::find_using_path(path)
-        } else {
-            BlenderExecutable::find()
+/// How thumbnails are rendered, see [`ConversionOptions::render_thumbnail`]
+///
+/// A camera is added looking down at the objects from the front right and placed so their
+/// bounding sphere fills the frame. Blends without lights get a sun light pointing the same way
+/// as the camera. The background is transparent.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct ThumbnailOptions {
+    /// Width of the image in pixels. Defaults to 256.
+    pub width: u32,
+    /// Height of the image in pixels. Defaults to 256.
+    pub height: u32,
+    /// The render engine to use. Defaults to [`RenderEngine::Cycles`].
+    pub engine: RenderEngine,
+    /// Number of render samples per pixel, ignored by [`RenderEngine::Workbench`]. Defaults to 16.
+    pub samples: u32,
+}
+
+impl Default for ThumbnailOptions {
+    fn default() -> Self {
+        Self {
+            width: 256,
+            height: 256,
+            engine: RenderEngine::Cycles,
+            samples: 16,
         }
     }
+}
 
-    /// Find tries [`BlenderExecutable::Normal`] then [`BlenderExecutable::Flatpak`] and returns
-    /// the first one that succeeds otherwise returns [`Error::MissingBlenderExecutable`]
-    pub fn find() -> Result<Self, Error> {
-        vec![Self::Normal, Self::Flatpak]
-            .into_iter()
-            .find(|x| matches!(x.test(), Ok(true)))
-            .ok_or(Error::MissingBlenderExecutable)
+impl ThumbnailOptions {
+    /// Python defining `render_thumbnail(filepath, include)`, which renders the renderable
+    /// objects `include` accepts to `filepath`
+    fn script(&self) -> String {
+        let engine = match self.engine {
+            RenderEngine::Eevee => {
+                "    # EEVEE was renamed in 4.2 then renamed back in 5.0
+    try:
+        render.engine = 'BLENDER_EEVEE_NEXT'
+    except TypeError:
+        render.engine = 'BLENDER_EEVEE'
+    scene.eevee.taa_render_samples = samples
+"
+            }
+            RenderEngine::Cycles => {
+                "    render.engine = 'CYCLES'
+    scene.cycles.device = 'CPU'
+    scene.cycles.samples = samples
+"
+            }
+            RenderEngine::Workbench => {
+                "    render.engine = 'BLENDER_WORKBENCH'
+"
+            }
+        };
+        format!(
+            "import math, mathutils
+def render_thumbnail(filepath, include):
+    scene = bpy.context.scene
+    renderable = [o for o in scene.objects if o.type in {{'MESH', 'CURVE', 'SURFACE', 'META', 'FONT'}} and not o.hide_render]
+    for o in renderable:
+        o.hide_render = not include(o)
+    corners = [o.matrix_world @ mathutils.Vector(c) for o in renderable if include(o) for c in o.bound_box]
+    if not corners:
+        return
+    lo = mathutils.Vector([min(c[i] for c in corners) for i in range(3)])
+    hi = mathutils.Vector([max(c[i] for c in corners) for i in range(3)])
+    center = (lo + hi) / 2
+    radius = max((hi - lo).length / 2, 1e-3)
+    width, height, samples = {width}, {height}, {samples}
+    camera_data = bpy.data.cameras.new('blend_converter_thumbnail')
+    camera = bpy.data.objects.new('blend_converter_thumbnail', camera_data)
+    scene.collection.objects.link(camera)
+    # The camera's angle spans the longer side of the image
+    fov = 2 * math.atan(math.tan(camera_data.angle / 2) * min(width, height) / max(width, height))
+    distance = radius / math.sin(fov / 2)
+    direction = mathutils.Vector((1.0, -1.0, 0.8)).normalized()
+    camera.location = center + direction * distance
+    camera.rotation_euler = (-direction).to_track_quat('-Z', 'Y').to_euler()
+    camera_data.clip_start = max(distance - radius, 1e-3) / 2
+    camera_data.clip_end = (distance + radius) * 2
+    scene.camera = camera
+    if not any(o.type == 'LIGHT' and not o.hide_render for o in scene.objects):
+        light = bpy.data.objects.new('blend_converter_thumbnail', bpy.data.lights.new('blend_converter_thumbnail', 'SUN'))
+        light.rotation_euler = camera.rotation_euler
+        scene.collection.objects.link(light)
+    render = scene.render
+{engine}    render.resolution_x = width
+    render.resolution_y = height
+    render.resolution_percentage = 100
+    render.film_transparent = True
+    render.image_settings.file_format = 'PNG'
+    render.filepath = filepath
+    bpy.ops.render.render(write_still=True)
+    print('{SIDECAR_MARKER}', filepath)
+",
+            width = self.width.max(1),
+            height = self.height.max(1),
+            samples = self.samples.max(1),
+        )
     }
+}
 
-    /// Only tries `path` as the blender executable and if it succeeds returns
-    /// [`BlenderExecutable::Path`] with the `path` otherwise returns
-    /// [`Error::MissingBlenderExecutable`]
-    pub fn find_using_path(path: &Path) -> Result<Self, Error> {
-        let s = Self::Path(path.to_path_buf());
-        if matches!(s.test(), Ok(true)) {
-            Ok(s)
-        } else {
-            Err(Error::MissingBlenderExecutable)
-        }
+/// A blender render engine, see [`ThumbnailOptions::engine`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum RenderEngine {
+    /// EEVEE, which is fast but needs a GPU so usually fails on headless servers
+    Eevee,
+    /// Cycles on the CPU, which works without a GPU
+    Cycles,
+    /// Workbench, blender's flat viewport shading
+    Workbench,
+}
+
+/// Post processing of exported glTF files, see [`ConversionOptions::post_process`]. Only applies
+/// to the glTF [`OutputFormat`]s. Optimization runs before the crate's own rewrites such as
+/// [`ConversionOptions::deterministic_ordering`], then validation and stats see the final files.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct PostProcess {
+    /// Parse each export with the `gltf` crate, which validates it against the glTF schema, and
+    /// load its buffers, failing with [`Error::GltfValidation`]. Defaults to false.
+    #[cfg(feature = "gltf")]
+    pub validate: bool,
+    /// Measure each export, returned in [`ConvertedBlend::stats`]. Defaults to false.
+    pub stats: bool,
+    /// Optimize each export with gltfpack. Defaults to None.
+    pub gltfpack: Option<Gltfpack>,
+}
+
+/// Running [gltfpack](https://github.com/zeux/meshoptimizer/tree/master/gltf), which must be
+/// installed, on each export. gltfpack removes unused data, merges meshes and materials where it
+/// can and rewrites the buffers. Node names, materials and extras are always kept.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct Gltfpack {
+    /// Quantize vertex attributes, which needs viewers to support `KHR_mesh_quantization`.
+    /// Defaults to false.
+    pub quantize: bool,
+    /// Extra command line arguments, such as `-si 0.5` to simplify meshes. Defaults to none.
+    pub args: Vec<String>,
+}
+
+/// What the exported glTF files of a blend contain, see [`PostProcess::stats`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GltfStats {
+    /// Number of meshes
+    pub meshes: usize,
+    /// Number of triangles across every mesh, counting each mesh once however many nodes use it
+    pub triangles: u64,
+    /// Size in bytes of the encoded images, which is less than the memory they take once decoded
+    pub image_bytes: u64,
+}
+
+impl std::ops::AddAssign for GltfStats {
+    fn add_assign(&mut self, other: Self) {
+        self.meshes += other.meshes;
+        self.triangles += other.triangles;
+        self.image_bytes += other.image_bytes;
     }
+}
 
-    fn cmd(&self) -> Command {
+/// The scenes exported from a blend, see [`ConversionOptions::export_scenes`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum SceneSelection {
+    /// Only the scene that was active when the blend was saved
+    #[default]
+    Active,
+    /// Every scene, each to its own file
+    All,
+}
+
+/// Where [`ConversionOptions::convert_dir`] writes each blend's export, see
+/// [`ConversionOptions::output_layout`]. Paths are given without the export's extension.
+#[derive(Clone, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum OutputLayout {
+    /// Blends keep their path within the input directory, under a directory named after the
+    /// input directory. Converting `assets/blends` to `out` writes `assets/blends/props/crate.blend`
+    /// to `out/blends/props/crate`.
+    #[default]
+    PreserveRelative,
+    /// Every blend is written directly into the output directory named after the blend. Blends
+    /// with the same name in different directories collide, see [`Error::OutputCollision`].
+    Flatten,
+    /// Maps each blend's path relative to the input directory to its output path relative to the
+    /// output directory
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Custom(Arc<dyn Fn(&Path) -> PathBuf + Send + Sync>),
+}
+
+impl std::fmt::Debug for OutputLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Normal => Command::new("blender"),
-            Self::Flatpak => {
-                let mut command = Command::new("flatpak");
-                command.arg("run").arg("org.blender.Blender");
-                command
+            Self::PreserveRelative => f.write_str("PreserveRelative"),
+            Self::Flatten => f.write_str("Flatten"),
+            Self::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+/// Custom layouts are equal when they share the same function
+impl PartialEq for OutputLayout {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::PreserveRelative, Self::PreserveRelative) | (Self::Flatten, Self::Flatten) => {
+                true
             }
-            Self::Path(path) => Command::new(path),
+            (Self::Custom(a), Self::Custom(b)) => Arc::ptr_eq(a, b),
+            _ => false,
         }
     }
+}
 
-    fn test(&self) -> std::io::Result<bool> {
-        Ok(self.cmd().arg("-b").arg("-v").status()?.success())
+impl OutputLayout {
+    /// The output path, without extension, of `input_path` found in `input_dir`
+    fn output_path(
+        &self,
+        input_dir: &Path,
+        input_path: &Path,
+        output_dir: &Path,
+    ) -> Option<PathBuf> {
+        let stem = input_path.file_stem()?;
+        let relative = input_path.strip_prefix(input_dir).unwrap_or(input_path);
+        Some(match self {
+            Self::PreserveRelative => {
+                let mut path = output_dir.to_path_buf();
+                if let Some(name) = input_dir.file_name() {
+                    path.push(name);
+                }
+                path.push(relative.parent().unwrap_or(Path::new("")));
+                path.join(stem)
+            }
+            Self::Flatten => output_dir.join(stem),
+            Self::Custom(map) => output_dir.join(map(relative)),
+        })
     }
 }
 
-/// Errors for converting blends
-#[derive(Debug, thiserror::Error)]
-pub enum Error {
-    /// Could not locate blender executable, see [`BlenderExecutable`] for the search strategy
-    #[error("could not locate blender executable, is blender in your path?")]
-    MissingBlenderExecutable,
-    /// Invalid input file blend. This error occurs when the file extension is not .blend
-    #[error("invalid input path {0:?}, path must have .blend file extension")]
-    InvalidInputFile(PathBuf),
-    /// Export failed with exit code
-    #[error("export failed with exit code {0}")]
-    Export(ExitStatus),
-    /// IOError when exporting
-    #[error("io error occurred: {0}")]
-    IOError(#[from] std::io::Error),
+/// What to do when a conversion would replace an existing export, see
+/// [`ConversionOptions::overwrite`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum OverwritePolicy {
+    /// Replace the existing export
+    #[default]
+    Overwrite,
+    /// Leave the existing export and skip the blend, even with [`ConversionOptions::force`].
+    /// [`ConversionOptions::convert`] then returns no files.
+    Skip,
+    /// Fail with [`Error::OutputExists`]
+    Error,
 }
 
-#[cfg(test)]
-mod tests {
-    use std::path::Path;
+/// The shape used by [`ConversionOptions::proxy_geometry`]
+///
+/// Proxies are built from the mesh after modifiers are evaluated and replace the object's mesh
+/// data, so the proxy has no materials, UVs, shape keys or modifiers. The object transform is
+/// kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ProxyKind {
+    /// A box spanning the object's bounds. The box is aligned to the object's local axes so it is
+    /// only tight for geometry aligned to them.
+    Box,
+    /// The convex hull of the object's vertices, computed with the same algorithm as blender's
+    /// Convex Hull operator. Concave regions such as holes and gaps are filled in.
+    ConvexHull,
+}
 
-    #[test]
-    fn export_test_blend() {
-        let options = crate::ConversionOptions::default();
-        let export_path = Path::new(".").canonicalize().expect("abs path").join("test.glb");
-        options.convert(Path::new("./test.blend"), &export_path).expect("convert blend");
-        assert!(matches!(export_path.try_exists(), Ok(true)));
+impl ProxyKind {
+    fn script(&self) -> String {
+        let build = match self {
+            Self::Box => {
+                "    lo = [min(c[i] for c in coords) for i in range(3)]
+    hi = [max(c[i] for c in coords) for i in range(3)]
+    corners = [(x, y, z) for x in (lo[0], hi[0]) for y in (lo[1], hi[1]) for z in (lo[2], hi[2])]
+    faces = [(0, 1, 3, 2), (4, 6, 7, 5), (0, 4, 5, 1), (2, 3, 7, 6), (0, 2, 6, 4), (1, 5, 7, 3)]
+    proxy.from_pydata(corners, [], faces)
+"
+            }
+            Self::ConvexHull => {
+                "    bm = bmesh.new()
+    for c in coords:
+        bm.verts.new(c)
+    hull = bmesh.ops.convex_hull(bm, input=bm.verts)
+    unused = [g for g in hull['geom_interior'] + hull['geom_unused'] if isinstance(g, bmesh.types.BMVert)]
+    bmesh.ops.delete(bm, geom=unused, context='VERTS')
+    bm.to_mesh(proxy)
+    bm.free()
+"
+            }
+        };
+        format!(
+            "import bmesh
+depsgraph = bpy.context.evaluated_depsgraph_get()
+for o in [o for o in bpy.data.objects if o.type == 'MESH']:
+    evaluated = o.evaluated_get(depsgraph)
+    coords = [tuple(v.co) for v in evaluated.to_mesh().vertices]
+    evaluated.to_mesh_clear()
+    if not coords:
+        continue
+    proxy = bpy.data.meshes.new(o.data.name + '.proxy')
+{build}    o.modifiers.clear()
+    o.data = proxy
+"
+        )
+    }
+}
+
+impl ConversionOptions {
+    /// Starts building options from the defaults, see [`ConversionOptionsBuilder`]
+    pub fn builder() -> ConversionOptionsBuilder {
+        ConversionOptionsBuilder::default()
+    }
+
+    /// The exporter used for the conversion, [`ConversionOptions::exporter`] if set otherwise
+    /// [`ConversionOptions::output_format`]
+    pub fn active_exporter(&self) -> &dyn Exporter {
+        match &self.exporter {
+            Some(exporter) => exporter.as_ref(),
+            None => &self.output_format,
+        }
+    }
+
+    /// The oldest blender version these options work with, if any option needs a newer blender
+    /// than the oldest supported, see [`ConversionOptions::min_version`]
+    fn required_version(&self) -> Option<(u32, u32, u32)> {
+        let gltf = self.exporter.is_none() && self.output_format.is_gltf();
+        [
+            self.min_version,
+            self.exporter
+                .is_none()
+                .then(|| self.output_format.min_blender_version())
+                .flatten(),
+            (gltf && self.animation_mode() != AnimationMode::Actions).then_some((3, 6, 0)),
+            (gltf && self.gltf.image_format == GltfImageFormat::Webp).then_some((4, 0, 0)),
+        ]
+        .into_iter()
+        .flatten()
+        .max()
+    }
+
+    /// These options with the output format matching the extension of `output`
+    fn for_output(&self, output: &Path) -> std::borrow::Cow<'_, Self> {
+        let output_format = self.output_format.for_output(output);
+        if self.exporter.is_some() || output_format == self.output_format {
+            std::borrow::Cow::Borrowed(self)
+        } else {
+            std::borrow::Cow::Owned(Self {
+                output_format,
+                ..self.clone()
+            })
+        }
+    }
+
+    /// [`AnimationOptions::mode`] unless [`ConversionOptions::nla_track_animations`] overrides it
+    fn animation_mode(&self) -> AnimationMode {
+        if self.nla_track_animations {
+            AnimationMode::NlaTracks
+        } else {
+            self.animation.mode
+        }
+    }
+
+    /// The script exporting to `file_path`. `existing_path` is where the outputs end up after the
+    /// conversion, which differs from `file_path` for atomic conversions.
+    fn export_script(&self, file_path: &Path, existing_path: &Path) -> String {
+        let exporter = self.active_exporter();
+        let mut script = String::from("import bpy\n");
+        // Paths of indirectly linked libraries are relative to the library linking them
+        script.push_str(&format!(
+            "import os
+for lib in bpy.data.libraries:
+    print('{LIBRARY_MARKER}', os.path.normpath(bpy.path.abspath(lib.filepath, library=lib.parent)))
+"
+        ));
+        let exported_path = exporter.exported_path(file_path);
+        if let Some(scene) = self
+            .scene
+            .as_ref()
+            .filter(|_| self.export_scenes != SceneSelection::All)
+        {
+            script.push_str(&format!(
+                "if {scene} not in bpy.data.scenes:
+    import sys
+    print('no scene named', {scene}, file=sys.stderr)
+    sys.exit({SCENE_NOT_FOUND})
+(bpy.context.window or bpy.context.window_manager.windows[0]).scene = bpy.data.scenes[{scene}]
+",
+                scene = format_py_str(scene)
+            ));
+        }
+        let gltf_selection =
+            self.gltf.use_selection && self.exporter.is_none() && self.output_format.is_gltf();
+        if gltf_selection {
+            // Pre export steps change the selection so remember it before they run
+            script.push_str(
+                "saved_selection = {o for o in bpy.context.view_layer.objects if o.select_get()}\n",
+            );
+        }
+        if let Some(pre_export_script) = &self.pre_export_script {
+            // Run with its own globals so the snippet's names can not clobber ours
+            script.push_str(&pre_export_script.exec_call("pre_export_script", "{'bpy': bpy}"));
+        }
+        if let Some(seed) = self.seed {
+            // Cycles seeds are limited to a signed 32 bit int
+            let cycles_seed = seed % (i32::MAX as u64 + 1);
+            script.push_str(&format!(
+                "import random
+random.seed({seed})
+for scene in bpy.data.scenes:
+    if hasattr(scene, 'cycles'):
+        scene.cycles.seed = {cycles_seed}
+        scene.cycles.use_animated_seed = False
+"
+            ));
+        }
+        for dir in &self.texture_search_dirs {
+            script.push_str(&format!(
+                "bpy.ops.file.find_missing_files(directory={})\n",
+                format_py_path(dir)
+            ));
+        }
+        if !self.texture_search_dirs.is_empty() || self.require_textures {
+            script.push_str(&format!(
+                "import os, sys
+missing = [i for i in bpy.data.images if i.source == 'FILE' and not i.packed_file and not os.path.exists(bpy.path.abspath(i.filepath, library=i.library))]
+for i in missing:
+    print(f'warning: image {{i.name!r}} not found at {{i.filepath!r}}', file=sys.stderr)
+if missing and {require}:
+    sys.exit({EXPORT_MISSING_TEXTURES})
+",
+                require = format_py_bool(self.require_textures),
+            ));
+        }
+        if self.strip_world {
+            script.push_str("for scene in bpy.data.scenes:\n    scene.world = None\n");
+        }
+        if self.unlit {
+            script.push_str(UNLIT_SCRIPT);
+        }
+        if self.warn_on_ngons {
+            script.push_str(&format!(
+                "import sys
+for me in {{o.data for o in bpy.data.objects if o.type == 'MESH'}}:
+    ngons = sum(1 for p in me.polygons if len(p.vertices) > 4)
+    if ngons:
+        print(f'warning: mesh {{me.name!r}} has {{ngons}} n-gons', file=sys.stderr)
+        print('{NGONS_MARKER}', ngons, me.name)
+"
+            ));
+        }
+        if self.triangulate {
+            script.push_str(
+                "import bmesh
+for me in {o.data for o in bpy.data.objects if o.type == 'MESH'}:
+    bm = bmesh.new()
+    bm.from_mesh(me)
+    bmesh.ops.triangulate(bm, faces=bm.faces[:])
+    bm.to_mesh(me)
+    bm.free()
+",
+            );
+        }
+        // Python conditions on an object `o` that must all hold for it to be exported
+        let mut filters = Vec::new();
+        if self.respect_hide_render {
+            filters.push("not o.hide_render".to_owned());
+        }
+        if self.visible_only {
+            filters.push("o.visible_get()".to_owned());
+        }
+        if !self.include_collections.is_empty() {
+            script.push_str(&format!(
+                "included = {{o for c in bpy.data.collections if c.name in {} for o in c.all_objects}}\n",
+                format_py_str_list(&self.include_collections)
+            ));
+            filters.push("o in included".to_owned());
+        }
+        if !self.exclude_name_patterns.is_empty() {
+            script.push_str(&format!(
+                "import fnmatch\nexcluded_patterns = {}\n",
+                format_py_str_list(&self.exclude_name_patterns)
+            ));
+            filters.push(
+                "not any(fnmatch.fnmatchcase(o.name, p) for p in excluded_patterns)".to_owned(),
+            );
+        }
+        if let Some((name, value)) = &self.required_property {
+            filters.push(format!(
+                "o.get({}) == {}",
+                format_py_str(name),
+                value.to_py()
+            ));
+        }
+        if self.exclude_cameras_and_lights {
+            filters.push("o.type not in {'CAMERA', 'LIGHT'}".to_owned());
+        }
+        if gltf_selection {
+            filters.push("o in saved_selection".to_owned());
+        }
+        let splitting = self.per_object || self.per_collection;
+        let selecting = !filters.is_empty() || self.collision_pattern.is_some() || splitting;
+        script.push_str("collision = set()\n");
+        if selecting {
+            let filters = if filters.is_empty() {
+                "True".to_owned()
+            } else {
+                filters.join(" and ")
+            };
+            script.push_str(&format!(
+                "def exportable(o):
+    return ({filters}) and o not in collision
+def select(predicate):
+    for o in bpy.context.view_layer.objects:
+        o.select_set(exportable(o) and predicate(o))
+"
+            ));
+        }
+        if self.flip_winding {
+            script.push_str(&format!(
+                "import bmesh
+for me in {{o.data for o in bpy.context.view_layer.objects if o.type == 'MESH' and {exportable}}}:
+    bm = bmesh.new()
+    bm.from_mesh(me)
+    bmesh.ops.reverse_faces(bm, faces=bm.faces[:])
+    bm.to_mesh(me)
+    bm.free()
+",
+                exportable = if selecting { "exportable(o)" } else { "True" },
+            ));
+        }
+        if let Some(proxy) = &self.proxy_geometry {
+            script.push_str(&proxy.script());
+        }
+        if let Some(lightmap) = &self.lightmap_uv {
+            script.push_str(&lightmap.script());
+        }
+        script.push_str(&self.animation.script());
+        if !self.material_remap.is_empty() {
+            let mut remap: Vec<_> = self.material_remap.iter().collect();
+            remap.sort();
+            let remap: Vec<String> = remap
+                .into_iter()
+                .map(|(from, to)| format!("{}: {}", format_py_str(from), format_py_str(to)))
+                .collect();
+            // Rename through temporary names first so swaps and chains do not collide
+            script.push_str(&format!(
+                "remap = {{{}}}
+renamed = [(m, remap[m.name]) for m in bpy.data.materials if m.name in remap]
+for i, (m, _) in enumerate(renamed):
+    m.name = '~blend_converter%d' % i
+for m, name in renamed:
+    m.name = name
+",
+                remap.join(", ")
+            ));
+        }
+        script.push_str(self.profile.script());
+
+        if self.scene_sidecar {
+            script.push_str(&format!(
+                "{SCENE_SIDECAR_SCRIPT}sidecar_path = {}
+write_scene_sidecar(sidecar_path)
+print('{SIDECAR_MARKER}', sidecar_path)
+",
+                format_py_path(&exported_path.with_extension("scene.json"))
+            ));
+        }
+        if self.manifest {
+            script.push_str(&format!(
+                "{MANIFEST_SCRIPT}manifest_path = {}
+write_manifest(manifest_path)
+print('{SIDECAR_MARKER}', manifest_path)
+",
+                format_py_path(&exported_path.with_extension("meta.json"))
+            ));
+        }
+        // Every export goes through `export` which reports the written path back to us
+        script.push_str(&format!(
+            "use_selection = {use_selection}
+exported_paths = []
+def export(filepath):
+{export_call}    print('{OUTPUT_MARKER}', filepath)
+    exported_paths.append(filepath)
+lods = {{}}
+",
+            use_selection = format_py_bool(selecting),
+            export_call = indent(&exporter.export_call("filepath", self), 1),
+        ));
+        if let Some(pattern) = &self.collision_pattern {
+            let collision_path = suffixed_path(&exported_path, "collision");
+            script.push_str(&format!(
+                "import fnmatch
+collision = {{o for o in bpy.context.view_layer.objects if fnmatch.fnmatchcase(o.name, {pattern})}}
+for o in bpy.context.view_layer.objects:
+    o.select_set(o in collision)
+export({collision_path})
+",
+                pattern = format_py_str(pattern),
+                collision_path = format_py_path(&collision_path),
+            ));
+        }
+        if self.required_property.is_some() {
+            script.push_str(&format!(
+                "if not any(exportable(o) for o in bpy.context.view_layer.objects):
+    import sys
+    print('no objects have the required property', file=sys.stderr)
+    sys.exit({NOTHING_TO_EXPORT})
+"
+            ));
+        }
+        if !self.lod_ratios.is_empty() {
+            let ratios: Vec<String> = self
+                .lod_ratios
+                .iter()
+                .map(|ratio| format!("{:?}", ratio.clamp(0.0, 1.0)))
+                .collect();
+            script.push_str(&format!(
+                "{LOD_SCRIPT}add_lods([{ratios}], [o for o in bpy.context.view_layer.objects if o.type == 'MESH' and o not in collision and {exportable}])
+for o, copies in lods.items():
+    print('{LOD_MARKER}', json.dumps([o.name] + [copy.name for copy in copies]))
+",
+                ratios = ratios.join(", "),
+                exportable = if selecting { "exportable(o)" } else { "True" },
+            ));
+        }
+        let all_scenes = self.export_scenes == SceneSelection::All;
+        let extension = format_py_str(&format!(".{}", exporter.extension()));
+        let (base, existing_base) = (
+            format_py_path(&exported_path.with_extension("")),
+            format_py_path(&exporter.exported_path(existing_path).with_extension("")),
+        );
+        if splitting || all_scenes {
+            script.push_str(SANITIZE_SCRIPT);
+        }
+        if splitting && self.skip_unchanged {
+            script.push_str(UNCHANGED_SCRIPT);
+        }
+        if all_scenes {
+            // Each scene is exported with the rest of the script bound to its own base path
+            script.push_str(&format!(
+                "window = bpy.context.window or bpy.context.window_manager.windows[0]
+used_scenes = set()
+for scene in list(bpy.data.scenes):
+    window.scene = scene
+    scene_name = unique_name(sanitize(scene.name), used_scenes)
+    base = {base} + '__' + scene_name
+    existing_base = {existing_base} + '__' + scene_name
+"
+            ));
+        }
+        let (base, existing_base) = if all_scenes {
+            ("base".to_owned(), "existing_base".to_owned())
+        } else {
+            (base, existing_base)
+        };
+        let mut export = String::new();
+        if self.per_object {
+            let skip = if self.skip_unchanged {
+                format!("    if unchanged(deps, {existing_base} + '.' + name + ext):\n        continue\n")
+            } else {
+                String::new()
+            };
+            export.push_str(&format!(
+                "used = set()
+ext = {extension}
+lod_copies = {{copy for copies in lods.values() for copy in copies}}
+for obj in [o for o in bpy.context.view_layer.objects if o.parent is None and exportable(o) and o not in lod_copies]:
+    deps = {{obj, *obj.children_recursive}}
+    deps |= {{m.object for o in list(deps) for m in getattr(o, 'modifiers', []) if m.type == 'ARMATURE' and m.object}}
+    deps |= {{copy for o in list(deps) for copy in lods.get(o, [])}}
+    name = unique_name(sanitize(obj.name), used)
+{skip}    select(lambda o: o in deps)
+    export({base} + '.' + name + ext)
+"
+            ));
+        } else if self.per_collection {
+            let skip = if self.skip_unchanged {
+                format!("    if unchanged(objects, {existing_base} + '.' + name + ext):\n        continue\n")
+            } else {
+                String::new()
+            };
+            export.push_str(&format!(
+                "used = set()
+ext = {extension}
+for coll in bpy.context.scene.collection.children:
+    objects = set(coll.all_objects)
+    if not any(exportable(o) for o in bpy.context.view_layer.objects if o in objects):
+        continue
+    name = unique_name(sanitize(coll.name), used)
+{skip}    select(lambda o: o in objects)
+    export({base} + '.' + name + ext)
+"
+            ));
+        } else {
+            if selecting {
+                export.push_str("select(lambda o: True)\n");
+            }
+            if all_scenes {
+                export.push_str(&format!("export(base + {extension})\n"));
+            } else {
+                export.push_str(&format!("export({})\n", format_py_path(&exported_path)));
+            }
+        }
+        if let Some(thumbnail) = &self.render_thumbnail {
+            script.push_str(&thumbnail.script());
+            export.push_str(&format!(
+                "render_thumbnail({base} + '.thumbnail.png', lambda o: {exportable} and o not in collision and all(o not in copies for copies in lods.values()))\n",
+                exportable = if selecting { "exportable(o)" } else { "True" },
+            ));
+        }
+        if all_scenes {
+            script.push_str(&indent(&export, 1));
+        } else {
+            script.push_str(&export);
+        }
+        if let Some(post_export_script) = &self.post_export_script {
+            script.push_str(&post_export_script.exec_call(
+                "post_export_script",
+                "{'bpy': bpy, 'exported_paths': list(exported_paths)}",
+            ));
+        }
+        script
+    }
+}
+
+/// The blends converted from a directory and the blends that failed
+type DirOutcome = (Vec<ConvertedBlend>, Vec<(PathBuf, Error)>);
+
+/// A message from a worker of [`run_jobs`] about the item at an index
+enum JobEvent<T> {
+    Started(usize),
+    Finished(usize, Result<T, Error>, Duration),
+}
+
+/// Runs `job` on each of `items` with up to `jobs` threads, passing their events to `on_event` on
+/// the calling thread. Once a job is cancelled, or fails without `keep_going`, no more jobs are
+/// started but running ones finish.
+fn run_jobs<I: Sync, T: Send>(
+    items: &[I],
+    jobs: usize,
+    keep_going: bool,
+    job: impl Fn(&I) -> Result<T, Error> + Sync,
+    mut on_event: impl FnMut(JobEvent<T>),
+) {
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let cancel = cancel::cancel_token();
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.min(items.len()) {
+            let (sender, next, stop, job) = (sender.clone(), &next, &stop, &job);
+            let cancel = cancel.clone();
+            scope.spawn(move || {
+                cancel::with_cancel_token(cancel, || {
+                    while !stop.load(Ordering::Relaxed) {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(index) else {
+                            break;
+                        };
+                        let _ = sender.send(JobEvent::Started(index));
+                        let start = Instant::now();
+                        let result = job(item);
+                        let cancelled = matches!(result, Err(Error::Cancelled));
+                        if cancelled || (result.is_err() && !keep_going) {
+                            stop.store(true, Ordering::Relaxed);
+                        }
+                        let _ = sender.send(JobEvent::Finished(index, result, start.elapsed()));
+                    }
+                })
+            });
+        }
+        drop(sender);
+        for event in receiver {
+            on_event(event);
+        }
+    });
+}
+
+/// What an export wrote and the warnings blender printed while exporting
+#[derive(Default)]
+struct Exported {
+    outputs: Vec<PathBuf>,
+    warnings: Vec<String>,
+    /// The linked libraries, see [`ConvertedBlend::dependencies`]
+    dependencies: Vec<PathBuf>,
+    /// The meshes with n-gons, see [`ConvertedBlend::ngons`]
+    ngons: Vec<(String, usize)>,
+}
+
+/// Prefix for the line printed by the export script for each file it writes
+const OUTPUT_MARKER: &str = "BLEND_CONVERTER_OUTPUT";
+
+/// Prefix for the line printed by the export script for each library the blend links, directly
+/// or through another library
+const LIBRARY_MARKER: &str = "BLEND_CONVERTER_LIBRARY";
+
+/// Prefix for the line printed by the export script for each additional file it writes that is
+/// not post processed
+const SIDECAR_MARKER: &str = "BLEND_CONVERTER_SIDECAR";
+
+/// Python writing the cameras and lights to a JSON file, see
+/// [`ConversionOptions::scene_sidecar`] for the schema
+const SCENE_SIDECAR_SCRIPT: &str = "import json
+def write_scene_sidecar(path):
+    def transform(o):
+        position, rotation, _ = o.matrix_world.decompose()
+        return {'name': o.name, 'position': list(position), 'rotation': list(rotation)}
+    cameras, lights = [], []
+    for o in bpy.context.view_layer.objects:
+        if o.type == 'CAMERA':
+            c = o.data
+            cameras.append({**transform(o), 'type': c.type, 'fov': c.angle, 'ortho_scale': c.ortho_scale, 'clip_start': c.clip_start, 'clip_end': c.clip_end})
+        elif o.type == 'LIGHT':
+            l = o.data
+            light = {**transform(o), 'type': l.type, 'color': list(l.color), 'energy': l.energy}
+            if l.type == 'SPOT':
+                light['spot_size'] = l.spot_size
+            lights.append(light)
+    with open(path, 'w') as f:
+        json.dump({'cameras': cameras, 'lights': lights}, f, indent=2)
+";
+
+/// Python writing what the blend contains to a JSON file, see [`ConversionOptions::manifest`] for
+/// the schema. Custom property values JSON can not represent, such as data blocks, are written
+/// as strings.
+const MANIFEST_SCRIPT: &str = "import json
+def write_manifest(path):
+    def value(v):
+        if hasattr(v, 'to_dict'):
+            return v.to_dict()
+        if hasattr(v, 'to_list'):
+            return v.to_list()
+        return v
+    def properties(id):
+        return {k: value(id[k]) for k in id.keys() if not k.startswith('_')}
+    scene = bpy.context.scene
+    objects = [{
+        'name': o.name,
+        'type': o.type,
+        'parent': o.parent.name if o.parent else None,
+        'collections': [c.name for c in o.users_collection],
+        'materials': [s.material.name for s in o.material_slots if s.material],
+        'properties': properties(o),
+    } for o in scene.objects]
+    collections = []
+    def add_children(collection, parent):
+        for c in collection.children:
+            collections.append({'name': c.name, 'parent': parent, 'properties': properties(c)})
+            add_children(c, c.name)
+    add_children(scene.collection, None)
+    users = {}
+    for o in scene.objects:
+        data = o.animation_data
+        if data:
+            strips = [s.action for t in data.nla_tracks for s in t.strips]
+            for a in [data.action] + strips:
+                if a:
+                    users.setdefault(a.name, set()).add(o.name)
+    animations = [{
+        'name': a.name,
+        'frame_start': a.frame_range[0],
+        'frame_end': a.frame_range[1],
+        'objects': sorted(users.get(a.name, ())),
+    } for a in bpy.data.actions]
+    with open(path, 'w') as f:
+        json.dump({'objects': objects, 'collections': collections, 'animations': animations}, f, indent=2, default=str)
+";
+
+/// Prefix for the line printed by the export script for each object with levels of detail,
+/// followed by a JSON array of the object name and the names of its levels
+const LOD_MARKER: &str = "BLEND_CONVERTER_LOD";
+
+/// Prefix for the line printed by the export script for each mesh with n-gons, followed by the
+/// number of n-gons and the mesh name
+const NGONS_MARKER: &str = "BLEND_CONVERTER_NGONS";
+
+/// Python adding decimated copies of objects as levels of detail. Only the decimate modifier is
+/// applied to the copies, their other modifiers are left for the exporter like the original's.
+const LOD_SCRIPT: &str = "import json
+def add_lods(ratios, objects):
+    view_layer = bpy.context.view_layer
+    for o in objects:
+        lods[o] = []
+        for level, ratio in enumerate(ratios, 1):
+            copy = o.copy()
+            copy.data = o.data.copy()
+            for c in o.users_collection:
+                c.objects.link(copy)
+            modifiers = [(m, m.show_viewport) for m in copy.modifiers]
+            for m, _ in modifiers:
+                m.show_viewport = False
+            decimate = copy.modifiers.new('blend_converter_lod', 'DECIMATE')
+            decimate.ratio = ratio
+            view_layer.update()
+            mesh = bpy.data.meshes.new_from_object(copy.evaluated_get(bpy.context.evaluated_depsgraph_get()))
+            copy.modifiers.remove(decimate)
+            for m, shown in modifiers:
+                m.show_viewport = shown
+            copy.data = mesh
+            copy.name = f'{o.name}.LOD{level}'
+            lods[o].append(copy)
+";
+
+/// Python helpers for turning blender names into file names. `sanitize` replaces characters that
+/// are not allowed in file names on common platforms and `unique_name` adds a numeric suffix if
+/// the name was already used, ignoring case for case insensitive filesystems.
+const SANITIZE_SCRIPT: &str = r#"import re
+def sanitize(name):
+    name = re.sub(r'[<>:"/\\|?*\x00-\x1f]', '_', name).rstrip('. ')
+    return name or '_'
+def unique_name(name, used):
+    candidate, i = name, 1
+    while candidate.lower() in used:
+        i += 1
+        candidate = f'{name}_{i}'
+    used.add(candidate.lower())
+    return candidate
+"#;
+
+/// Python helper deciding whether the objects `objs` are older than their existing output. Data
+/// linked from a library is compared with the library file, local data with the blend.
+const UNCHANGED_SCRIPT: &str = "import os
+def unchanged(objs, existing):
+    if not os.path.exists(existing):
+        return False
+    libraries = {d.library for o in objs for d in (o, o.data) if d is not None}
+    sources = [bpy.path.abspath(l.filepath, library=l.library) if l else bpy.data.filepath for l in libraries]
+    return all(os.path.exists(s) and os.path.getmtime(s) <= os.path.getmtime(existing) for s in sources)
+";
+
+/// Inserts `.suffix` before the extension of `path` e.g. `a/b.glb` becomes `a/b.suffix.glb`
+fn suffixed_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = path.file_stem().unwrap_or_default().to_owned();
+    file_name.push(".");
+    file_name.push(suffix);
+    if let Some(extension) = path.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+    path.with_file_name(file_name)
+}
+
+/// Replaces principled BSDF surfaces with a background shader so the glTF exporter detects them as
+/// unlit
+const UNLIT_SCRIPT: &str = "for m in bpy.data.materials:
+    if not m.use_nodes:
+        continue
+    nodes, links = m.node_tree.nodes, m.node_tree.links
+    out = next((n for n in nodes if n.type == 'OUTPUT_MATERIAL' and n.is_active_output), None)
+    if out is None or not out.inputs['Surface'].is_linked:
+        continue
+    bsdf = out.inputs['Surface'].links[0].from_node
+    if bsdf.type != 'BSDF_PRINCIPLED':
+        continue
+    bg = nodes.new('ShaderNodeBackground')
+    base = bsdf.inputs['Base Color']
+    if base.is_linked:
+        links.new(base.links[0].from_socket, bg.inputs['Color'])
+    else:
+        bg.inputs['Color'].default_value = base.default_value
+    links.new(bg.outputs['Background'], out.inputs['Surface'])
+";
+
+impl ConversionOptions {
+    /// Create a new ConversionOptions with default output format of [`OutputFormat::Glb`].
+    ///
+    /// This is equivalent to [`ConversionOptions::default`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Walks a directory and converts all the blend files while preserving the directory
+    /// structure. Blends with up to date outputs are skipped, see [`ConversionOptions::force`].
+    ///
+    /// Returns the files written for each converted blend, skipped blends are not included.
+    pub fn convert_dir(
+        &self,
+        input_dir: &Path,
+        output_dir: &Path,
+    ) -> Result<Vec<ConvertedBlend>, Error> {
+        self.convert_dir_with_on_error(input_dir, output_dir, |_, _| {})
+    }
+
+    /// Same as [`ConversionOptions::convert_dir`] but calls `on_error` with the input path and
+    /// error as soon as a blend fails to convert, before the error is returned.
+    pub fn convert_dir_with_on_error(
+        &self,
+        input_dir: &Path,
+        output_dir: &Path,
+        on_error: impl FnMut(&Path, &Error),
+    ) -> Result<Vec<ConvertedBlend>, Error> {
+        let (converted, failed) =
+            self.convert_dir_with_callbacks(input_dir, output_dir, false, failed_only(on_error))?;
+        match failed.into_iter().next() {
+            Some((_, err)) => Err(err),
+            None => Ok(converted),
+        }
+    }
+
+    /// Same as [`ConversionOptions::convert_dir`] but calls `on_event` as the conversion
+    /// progresses, see [`ConversionEvent`] for the order events are sent in.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use blend_converter::ConversionEvent;
+    ///
+    /// let mut total = 0;
+    /// let mut done = 0;
+    /// blend_converter::ConversionOptions::default()
+    ///     .convert_dir_with_progress(Path::new("blends"), Path::new("gltfs"), |event| match event {
+    ///         ConversionEvent::Total(count) => total = count,
+    ///         ConversionEvent::FileStarted { path } => {
+    ///             println!("cargo:warning=converting {}/{total} {}", done + 1, path.display());
+    ///         }
+    ///         _ => done += 1,
+    ///     })
+    ///     .expect("failed to convert blends");
+    /// ```
+    pub fn convert_dir_with_progress(
+        &self,
+        input_dir: &Path,
+        output_dir: &Path,
+        on_event: impl FnMut(ConversionEvent<'_>),
+    ) -> Result<Vec<ConvertedBlend>, Error> {
+        let (converted, failed) =
+            self.convert_dir_with_callbacks(input_dir, output_dir, false, on_event)?;
+        match failed.into_iter().next() {
+            Some((_, err)) => Err(err),
+            None => Ok(converted),
+        }
+    }
+
+    /// Same as [`ConversionOptions::convert_dir`] but also returns the blends that were skipped and
+    /// how long converting the whole directory took
+    pub fn convert_dir_summary(
+        &self,
+        input_dir: &Path,
+        output_dir: &Path,
+    ) -> Result<DirConversionSummary, Error> {
+        let start = Instant::now();
+        let mut skipped = Vec::new();
+        let on_event = |event: ConversionEvent<'_>| {
+            if let ConversionEvent::FileSkipped { path } = event {
+                skipped.push(path.to_path_buf());
+            }
+        };
+        let converted = self.convert_dir_with_progress(input_dir, output_dir, on_event)?;
+        Ok(DirConversionSummary {
+            converted,
+            skipped,
+            duration: start.elapsed(),
+        })
+    }
+
+    /// Same as [`ConversionOptions::convert_dir`] but keeps converting after a blend fails and
+    /// collects the failures into the returned report, so one broken blend does not hide problems
+    /// with the rest. [`ConversionReport::succeeded`] has the blends that were converted, skipped
+    /// blends are not included. With [`ConversionOptions::batch`] the batch still stops at the
+    /// first failure.
+    pub fn convert_dir_report(
+        &self,
+        input_dir: &Path,
+        output_dir: &Path,
+    ) -> Result<ConversionReport, Error> {
+        let (converted, failed) =
+            self.convert_dir_with_callbacks(input_dir, output_dir, true, |_| {})?;
+        Ok(ConversionReport {
+            succeeded: converted.into_iter().map(|blend| blend.input).collect(),
+            failed,
+        })
+    }
+
+    /// Works out what [`ConversionOptions::convert_dir`] would do without converting anything or
+    /// starting blender. Every file in `input_dir` is listed along with where it would be
+    /// exported to and, if it would not be converted, why. With
+    /// [`ConversionOptions::incremental`] every blend is read to check the cache.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    ///
+    /// let plan = blend_converter::ConversionOptions::default()
+    ///     .plan_dir(Path::new("blends"), Path::new("gltfs"))
+    ///     .expect("failed to plan conversion");
+    /// for file in plan.to_convert() {
+    ///     println!("{} -> {}", file.input.display(), file.output.display());
+    /// }
+    /// plan.execute().expect("failed to convert blends");
+    /// ```
+    pub fn plan_dir(&self, input_dir: &Path, output_dir: &Path) -> Result<ConversionPlan, Error> {
+        let cache = self
+            .incremental
+            .then(|| Cache::load(output_dir, self))
+            .filter(|_| !self.force);
+        let mut files = Vec::new();
+        for entry in WalkDir::new(input_dir)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+        {
+            if !entry.metadata().is_ok_and(|m| m.is_file()) {
+                continue;
+            }
+            let input = entry.into_path();
+            if input.extension() != Some(OsStr::new("blend")) {
+                files.push(PlannedFile {
+                    output: PathBuf::new(),
+                    skip: Some(SkipReason::NotBlend),
+                    input,
+                    hash: None,
+                });
+                continue;
+            }
+            let output_path = self.dir_entry_path(input_dir, &input, output_dir)?;
+            let output = self.active_exporter().exported_path(&output_path);
+            let hash = self.incremental.then(|| content_hash(&input)).transpose()?;
+            let skip = if self.overwrite == OverwritePolicy::Skip && output.exists() {
+                Some(SkipReason::OutputExists)
+            } else if let Some(hash) = &hash {
+                let key = cache_key(input_dir, &input);
+                let fresh = cache
+                    .as_ref()
+                    .is_some_and(|cache| cache.is_fresh(&key, hash, &output));
+                fresh.then_some(SkipReason::Cached)
+            } else {
+                let fresh = self.is_up_to_date(&input, &output_path);
+                fresh.then_some(SkipReason::UpToDate)
+            };
+            files.push(PlannedFile {
+                input,
+                output,
+                skip,
+                hash,
+            });
+        }
+        Ok(ConversionPlan {
+            options: self.clone(),
+            input_dir: input_dir.to_path_buf(),
+            output_dir: output_dir.to_path_buf(),
+            files,
+        })
+    }
+
+    /// Converts the blends in `input_dir` returning the converted blends and the failures. Unless
+    /// `keep_going` is set there is at most one failure, after which no more blends are started.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "convert_dir",
+            skip_all,
+            fields(input_dir = %input_dir.display(), output_dir = %output_dir.display())
+        )
+    )]
+    fn convert_dir_with_callbacks(
+        &self,
+        input_dir: &Path,
+        output_dir: &Path,
+        keep_going: bool,
+        on_event: impl FnMut(ConversionEvent<'_>),
+    ) -> Result<DirOutcome, Error> {
+        let mut on_event = traced(on_event);
+        // Walk first so the total is known before converting
+        let input_paths: Vec<PathBuf> = blend_files(input_dir).collect();
+        on_event(ConversionEvent::Total(input_paths.len()));
+        if !self.incremental {
+            return self.convert_paths(input_paths, input_dir, output_dir, keep_going, on_event);
+        }
+
+        let mut cache = Cache::load(output_dir, self);
+        let mut blends = Vec::with_capacity(input_paths.len());
+        let mut stale = Vec::new();
+        let mut failed = Vec::new();
+        for input_path in input_paths {
+            let key = cache_key(input_dir, &input_path);
+            let hashed = content_hash(&input_path).and_then(|hash| {
+                let output = self.dir_entry_path(input_dir, &input_path, output_dir)?;
+                Ok((hash, self.active_exporter().exported_path(&output)))
+            });
+            let (hash, output) = match hashed {
+                Ok(hashed) => hashed,
+                Err(err) => {
+                    on_event(ConversionEvent::FileStarted { path: &input_path });
+                    on_event(ConversionEvent::FileFailed {
+                        path: &input_path,
+                        error: &err,
+                    });
+                    failed.push((input_path, err));
+                    if keep_going {
+                        continue;
+                    }
+                    return Ok((Vec::new(), failed));
+                }
+            };
+            if self.force || !cache.is_fresh(&key, &hash, &output) {
+                stale.push(input_path.clone());
+            } else {
+                on_event(ConversionEvent::FileStarted { path: &input_path });
+                on_event(ConversionEvent::FileSkipped { path: &input_path });
+            }
+            blends.push((input_path, key, hash, output));
+        }
+        // The cache replaces the modification time check
+        let options = ConversionOptions {
+            force: true,
+            ..self.clone()
+        };
+        // Nothing to convert so there is no need to find blender either
+        let (converted, mut conversion_failed) = if stale.is_empty() {
+            (Vec::new(), Vec::new())
+        } else {
+            options.convert_paths(stale, input_dir, output_dir, keep_going, on_event)?
+        };
+        for blend in &converted {
+            if let Some((_, key, hash, output)) =
+                blends.iter().find(|(path, ..)| *path == blend.input)
+            {
+                cache.insert(
+                    key.clone(),
+                    hash,
+                    output,
+                    &blend.outputs,
+                    &blend.dependencies,
+                );
+            }
+        }
+        let keys: Vec<String> = blends.into_iter().map(|(_, key, ..)| key).collect();
+        cache.retain(&keys);
+        cache.save()?;
+        failed.append(&mut conversion_failed);
+        Ok((converted, failed))
+    }
+
+    /// Converts `input_paths` found in `input_dir`
+    fn convert_paths(
+        &self,
+        input_paths: Vec<PathBuf>,
+        input_dir: &Path,
+        output_dir: &Path,
+        keep_going: bool,
+        mut on_event: impl FnMut(ConversionEvent<'_>),
+    ) -> Result<DirOutcome, Error> {
+        self.check_collisions(&input_paths, input_dir, output_dir)?;
+        let blender_exe = BlenderExecutable::find_using_options(self)?;
+        if self.batch {
+            return self.convert_dir_batch(
+                input_paths,
+                input_dir,
+                output_dir,
+                &blender_exe,
+                on_event,
+            );
+        }
+        if let Some(jobs) = self.jobs.filter(|&jobs| jobs > 1) {
+            return Ok(self.convert_dir_parallel(
+                input_paths,
+                input_dir,
+                output_dir,
+                jobs,
+                keep_going,
+                &blender_exe,
+                on_event,
+            ));
+        }
+        let mut converted = Vec::new();
+        let mut failed = Vec::new();
+        for input_path in input_paths {
+            on_event(ConversionEvent::FileStarted { path: &input_path });
+            let start = Instant::now();
+            match self.convert_dir_entry(input_dir, &input_path, output_dir, &blender_exe) {
+                Ok(Some(exported)) => {
+                    let blend = ConvertedBlend::new(self, input_path, exported, start.elapsed());
+                    on_event(ConversionEvent::FileFinished {
+                        path: &blend.input,
+                        outputs: &blend.outputs,
+                        duration: blend.duration,
+                    });
+                    converted.push(blend);
+                }
+                Ok(None) => on_event(ConversionEvent::FileSkipped { path: &input_path }),
+                Err(err) => {
+                    on_event(ConversionEvent::FileFailed {
+                        path: &input_path,
+                        error: &err,
+                    });
+                    let cancelled = matches!(err, Error::Cancelled);
+                    failed.push((input_path, err));
+                    if !keep_going || cancelled {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok((converted, failed))
+    }
+
+    /// Converts `input_paths` with up to `jobs` blender processes at once, see
+    /// [`ConversionOptions::jobs`]
+    #[allow(clippy::too_many_arguments)]
+    fn convert_dir_parallel(
+        &self,
+        input_paths: Vec<PathBuf>,
+        input_dir: &Path,
+        output_dir: &Path,
+        jobs: usize,
+        keep_going: bool,
+        blender_exe: &BlenderExecutable,
+        mut on_event: impl FnMut(ConversionEvent<'_>),
+    ) -> DirOutcome {
+        // Output directories are created up front so workers never race creating shared parents
+        let mut entries = Vec::with_capacity(input_paths.len());
+        let mut failed = Vec::new();
+        for input_path in input_paths {
+            match self.dir_entry_output(input_dir, &input_path, output_dir) {
+                Ok(output_path) => entries.push((input_path, output_path)),
+                Err(err) => {
+                    on_event(ConversionEvent::FileStarted { path: &input_path });
+                    on_event(ConversionEvent::FileFailed {
+                        path: &input_path,
+                        error: &err,
+                    });
+                    failed.push((input_path, err));
+                    if !keep_going {
+                        return (Vec::new(), failed);
+                    }
+                }
+            }
+        }
+        // Concurrent exports into one directory can only be told apart when each is staged
+        let options = ConversionOptions {
+            atomic: true,
+            ..self.clone()
+        };
+        let mut converted = Vec::new();
+        run_jobs(
+            &entries,
+            jobs,
+            keep_going,
+            |(input_path, output_path)| {
+                if options.is_up_to_date(input_path, output_path) {
+                    Ok(None)
+                } else {
+                    options
+                        .convert_internal(input_path, output_path, blender_exe)
+                        .map(Some)
+                }
+            },
+            |event| match event {
+                JobEvent::Started(index) => on_event(ConversionEvent::FileStarted {
+                    path: &entries[index].0,
+                }),
+                JobEvent::Finished(index, Ok(Some(exported)), duration) => {
+                    let blend =
+                        ConvertedBlend::new(self, entries[index].0.clone(), exported, duration);
+                    on_event(ConversionEvent::FileFinished {
+                        path: &blend.input,
+                        outputs: &blend.outputs,
+                        duration,
+                    });
+                    converted.push((index, blend));
+                }
+                JobEvent::Finished(index, Ok(None), _) => on_event(ConversionEvent::FileSkipped {
+                    path: &entries[index].0,
+                }),
+                JobEvent::Finished(index, Err(err), _) => {
+                    on_event(ConversionEvent::FileFailed {
+                        path: &entries[index].0,
+                        error: &err,
+                    });
+                    failed.push((entries[index].0.clone(), err));
+                }
+            },
+        );
+        converted.sort_by_key(|(index, _)| *index);
+        (
+            converted.into_iter().map(|(_, blend)| blend).collect(),
+            failed,
+        )
+    }
+
+    fn convert_dir_entry(
+        &self,
+        input_dir: &Path,
+        input_path: &Path,
+        output_dir: &Path,
+        blender_exe: &BlenderExecutable,
+    ) -> Result<Option<Exported>, Error> {
+        let output_path = self.dir_entry_output(input_dir, input_path, output_dir)?;
+        if self.is_up_to_date(input_path, &output_path) {
+            return Ok(None);
+        }
+        self.convert_internal(input_path, &output_path, blender_exe)
+            .map(Some)
+    }
+
+    /// The output path, without extension, for `input_path` found in a directory walk of
+    /// `input_dir`, see [`ConversionOptions::output_layout`]. The output's directory is created.
+    fn dir_entry_output(
+        &self,
+        input_dir: &Path,
+        input_path: &Path,
+        output_dir: &Path,
+    ) -> Result<PathBuf, Error> {
+        let output_path = self.dir_entry_path(input_dir, input_path, output_dir)?;
+        if let Some(parent) = output_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(output_path)
+    }
+
+    /// [`ConversionOptions::dir_entry_output`] without creating the output's directory
+    fn dir_entry_path(
+        &self,
+        input_dir: &Path,
+        input_path: &Path,
+        output_dir: &Path,
+    ) -> Result<PathBuf, Error> {
+        self.output_layout
+            .output_path(input_dir, input_path, output_dir)
+            .ok_or(Error::InvalidInputFile(input_path.to_path_buf()))
+    }
+
+    /// Fails if two of `input_paths` would be exported to the same path, see
+    /// [`Error::OutputCollision`]
+    fn check_collisions(
+        &self,
+        input_paths: &[PathBuf],
+        input_dir: &Path,
+        output_dir: &Path,
+    ) -> Result<(), Error> {
+        let mut outputs = HashMap::new();
+        for input_path in input_paths {
+            // Invalid paths fail when they are converted
+            let Ok(output) = self.dir_entry_path(input_dir, input_path, output_dir) else {
+                continue;
+            };
+            if let Some(first) = outputs.insert(output.clone(), input_path) {
+                return Err(Error::OutputCollision {
+                    output,
+                    first: first.clone(),
+                    second: input_path.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the output of converting `input_path` to `output_path` is newer than the blend,
+    /// see [`ConversionOptions::force`], or exists and must not be replaced, see
+    /// [`OverwritePolicy::Skip`]
+    fn is_up_to_date(&self, input_path: &Path, output_path: &Path) -> bool {
+        let exported_path = self.active_exporter().exported_path(output_path);
+        if self.overwrite == OverwritePolicy::Skip && exported_path.exists() {
+            return true;
+        }
+        if self.force {
+            return false;
+        }
+        let modified = |path: &Path| path.metadata().and_then(|m| m.modified()).ok();
+        let (Some(input_modified), Some(output_modified)) =
+            (modified(input_path), modified(&exported_path))
+        else {
+            return false;
+        };
+        let siblings_exist = self.exporter.is_some()
+            || self.output_format != OutputFormat::GltfSeparate
+            || exported_path.with_extension("bin").is_file();
+        output_modified > input_modified && siblings_exist
+    }
+
+    /// Converts every blend in `input_paths` with one blender process, see
+    /// [`ConversionOptions::batch`]
+    fn convert_dir_batch(
+        &self,
+        input_paths: Vec<PathBuf>,
+        input_dir: &Path,
+        output_dir: &Path,
+        blender_exe: &BlenderExecutable,
+        mut on_event: impl FnMut(ConversionEvent<'_>),
+    ) -> Result<DirOutcome, Error> {
+        let mut jobs = Vec::new();
+        for input_path in input_paths {
+            on_event(ConversionEvent::FileStarted { path: &input_path });
+            let job = self
+                .dir_entry_output(input_dir, &input_path, output_dir)
+                .and_then(|output_path| Ok((checked_input_path(&input_path)?, output_path)));
+            match job {
+                Ok((_, output_path)) if self.is_up_to_date(&input_path, &output_path) => {
+                    on_event(ConversionEvent::FileSkipped { path: &input_path });
+                }
+                Ok((input_file_path, output_path)) => {
+                    jobs.push((input_path, input_file_path, output_path))
+                }
+                Err(err) => {
+                    on_event(ConversionEvent::FileFailed {
+                        path: &input_path,
+                        error: &err,
+                    });
+                    return Ok((Vec::new(), vec![(input_path, err)]));
+                }
+            }
+        }
+        if jobs.is_empty() {
+            return Ok((Vec::new(), Vec::new()));
+        }
+
+        let mut script = String::from("jobs = [\n");
+        for (_, input_file_path, output_path) in &jobs {
+            script.push_str(&format!(
+                "    ({}, {}),\n",
+                format_py_path(input_file_path),
+                format_py_str(&self.export_script(output_path, output_path)),
+            ));
+        }
+        script.push_str(&format!(
+            "]
+import bpy, traceback
+for i, (input, script) in enumerate(jobs):
+    print('{BATCH_START_MARKER}', i)
+    try:
+        bpy.ops.wm.open_mainfile(filepath=input, load_ui=False)
+        exec(script, {{}})
+        code = 0
+    except SystemExit as e:
+        code = e.code if isinstance(e.code, int) else int(e.code is not None)
+    except Exception:
+        traceback.print_exc()
+        code = 10
+    print('{BATCH_END_MARKER}', i, code)
+    if code != 0:
+        break
+"
+        ));
+        // The script embeds every job's script so it can be too long for a command line
+        let script_path = unique_temp_path("batch.py");
+        std::fs::write(&script_path, script)?;
+        let start = Instant::now();
+        let output = self.spawn_blender(
+            blender_exe,
+            None,
+            &format!(
+                "exec(compile(open({path}, encoding='utf-8').read(), {path}, 'exec'))",
+                path = format_py_path(&script_path)
+            ),
+        );
+        let _ = std::fs::remove_file(&script_path);
+        let output = output?;
+
+        let mut job_outputs = output.batch_jobs().into_iter();
+        let mut converted = Vec::with_capacity(jobs.len());
+        for (input_path, input_file_path, _) in jobs {
+            let result = match job_outputs.next() {
+                Some(job_output) => self.finish_export(&input_file_path, &job_output),
+                // A timed out blender is killed so it would also look out of memory
+                None if output.timed_out => Err(Error::Timeout(input_file_path.clone())),
+                None if self.is_out_of_memory(&output) => {
+                    Err(Error::OutOfMemory(input_file_path.clone()))
+                }
+                // Blender exited before finishing this job
+                None => Err(output.export_error()),
+            };
+            match result {
+                Ok(exported) => {
+                    let blend = ConvertedBlend::new(self, input_path, exported, start.elapsed());
+                    on_event(ConversionEvent::FileFinished {
+                        path: &blend.input,
+                        outputs: &blend.outputs,
+                        duration: blend.duration,
+                    });
+                    converted.push(blend);
+                }
+                Err(err) => {
+                    on_event(ConversionEvent::FileFailed {
+                        path: &input_path,
+                        error: &err,
+                    });
+                    return Ok((converted, vec![(input_path, err)]));
+                }
+            }
+        }
+        Ok((converted, Vec::new()))
+    }
+
+    /// Walks a directory converts all the blend files while preserving the directory structure but
+    /// outputs them to OUT_DIR.
+    ///
+    /// For use in build scripts only. Prints `cargo:rerun-if-changed` for the libraries the blends
+    /// link, see [`ConvertedBlend::dependencies`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    ///
+    /// let input_dir = Path::new("blends");
+    /// blend_converter::ConversionOptions::default()
+    ///     .convert_dir_build_script(input_dir)
+    ///     .expect("failed to convert blends");
+    /// println!("cargo:rerun-if-changed={}", input_dir.display());
+    /// println!("cargo:rerun-if-changed=build.rs");
+    /// ```
+    pub fn convert_dir_build_script(&self, input_dir: &Path) -> Result<Vec<ConvertedBlend>, Error> {
+        let output_dir_env =
+            env::var("OUT_DIR").expect("OUT_DIR is not set, this must be called from build.rs");
+        let output_dir = Path::new(&output_dir_env);
+        let converted = self.convert_dir(input_dir, output_dir)?;
+        let mut dependencies: std::collections::BTreeSet<_> = converted
+            .iter()
+            .flat_map(|blend| blend.dependencies.iter().cloned())
+            .collect();
+        if self.incremental {
+            // Skipped blends are not in `converted`, the cache has their libraries
+            dependencies.extend(Cache::load(output_dir, self).dependencies());
+        }
+        for dependency in dependencies {
+            println!("cargo:rerun-if-changed={}", dependency.display());
+        }
+        Ok(converted)
+    }
+
+    /// Convert an individual blend file
+    ///
+    /// If `output` has the extension of one of the formats the matching format is used whatever
+    /// [`ConversionOptions::output_format`] is, see [`OutputFormat::for_output`]. A custom
+    /// [`ConversionOptions::exporter`] is always used as is.
+    ///
+    /// Returns every file written, the main export first followed by any sidecar or extra files.
+    pub fn convert(&self, input: &Path, output: &Path) -> Result<Vec<PathBuf>, Error> {
+        let options = self.for_output(output);
+        let blender_exe = BlenderExecutable::find_using_options(&options)?;
+        Ok(options
+            .convert_internal(input, output, &blender_exe)?
+            .outputs)
+    }
+
+    /// Same as [`ConversionOptions::convert`] but also returns the size of each written file,
+    /// how long the conversion took and the warnings blender printed.
+    pub fn convert_detailed(&self, input: &Path, output: &Path) -> Result<ConvertedBlend, Error> {
+        let start = Instant::now();
+        let options = self.for_output(output);
+        let blender_exe = BlenderExecutable::find_using_options(&options)?;
+        let exported = options.convert_internal(input, output, &blender_exe)?;
+        Ok(ConvertedBlend::new(
+            &options,
+            input.to_path_buf(),
+            exported,
+            start.elapsed(),
+        ))
+    }
+
+    /// Converts the blends in `input_dir` like [`ConversionOptions::convert_dir_with_progress`]
+    /// then keeps running, converting blends again whenever they are saved. Changes arriving
+    /// together are converted as one batch that starts with its own [`ConversionEvent::Total`].
+    /// Blender's `.blend1` backups and the temporary files it saves through are ignored. Blends
+    /// that fail to convert are reported to `on_event` without stopping the watch.
+    ///
+    /// Only returns if watching the directory or finding blender fails.
+    #[cfg(feature = "watch")]
+    pub fn watch_dir(
+        &self,
+        input_dir: &Path,
+        output_dir: &Path,
+        mut on_event: impl FnMut(ConversionEvent<'_>),
+    ) -> Result<(), Error> {
+        use notify::Watcher;
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        let watched = input_dir.canonicalize()?;
+        // Watching starts first so blends saved during the first conversion are not missed
+        watcher.watch(&watched, notify::RecursiveMode::Recursive)?;
+        self.convert_dir_with_callbacks(input_dir, output_dir, true, &mut on_event)?;
+        while let Ok(event) = receiver.recv() {
+            let mut changed = std::collections::BTreeSet::new();
+            let mut next = Some(event);
+            while let Some(event) = next {
+                for path in watch::changed_blends(&event?) {
+                    changed.insert(watch::input_path(input_dir, &watched, path));
+                }
+                next = receiver.recv_timeout(watch::DEBOUNCE).ok();
+            }
+            let changed: Vec<PathBuf> = changed.into_iter().filter(|p| p.is_file()).collect();
+            if changed.is_empty() {
+                continue;
+            }
+            on_event(ConversionEvent::Total(changed.len()));
+            self.convert_paths(changed, input_dir, output_dir, true, &mut on_event)?;
+        }
+        Ok(())
+    }
+
+    /// Async version of [`ConversionOptions::convert`] that converts on tokio's blocking thread
+    /// pool so the executor is never blocked. Dropping the future cancels the conversion, killing
+    /// blender.
+    ///
+    /// Blender is run and waited on by the blocking conversion rather than through
+    /// `tokio::process`, so each conversion in flight holds one of tokio's blocking threads until
+    /// blender exits. Keep the number of concurrent conversions below the runtime's
+    /// `max_blocking_threads` (512 by default) or later ones wait for a free thread.
+    #[cfg(feature = "tokio")]
+    pub async fn convert_async(&self, input: &Path, output: &Path) -> Result<Vec<PathBuf>, Error> {
+        let (options, input, output) = (self.clone(), input.to_owned(), output.to_owned());
+        cancel::run_blocking(move || options.convert(&input, &output)).await
+    }
+
+    /// Async version of [`ConversionOptions::convert_dir`], see
+    /// [`ConversionOptions::convert_async`]. Dropping the future kills the running blender
+    /// processes and converts no more blends, blends already converted are kept. The whole
+    /// directory holds one blocking thread, and with [`ConversionOptions::jobs`] it starts its
+    /// own threads for the extra blenders outside tokio's pool.
+    #[cfg(feature = "tokio")]
+    pub async fn convert_dir_async(
+        &self,
+        input_dir: &Path,
+        output_dir: &Path,
+    ) -> Result<Vec<ConvertedBlend>, Error> {
+        let (options, input_dir, output_dir) =
+            (self.clone(), input_dir.to_owned(), output_dir.to_owned());
+        cancel::run_blocking(move || options.convert_dir(&input_dir, &output_dir)).await
+    }
+
+    /// Convert an individual blend file and return each written file with the hex encoded
+    /// SHA-256 hash of its contents, hashed after post processing. Combine with
+    /// [`ConversionOptions::deterministic_ordering`] for hashes that only change when the content
+    /// does. The output format is chosen like [`ConversionOptions::convert`].
+    #[cfg(feature = "sha2")]
+    pub fn convert_hashed(
+        &self,
+        input: &Path,
+        output: &Path,
+    ) -> Result<Vec<(PathBuf, String)>, Error> {
+        let options = self.for_output(output);
+        let blender_exe = BlenderExecutable::find_using_options(&options)?;
+        options
+            .convert_internal(input, output, &blender_exe)?
+            .outputs
+            .into_iter()
+            .map(|path| {
+                let hash = sha256_hex(&std::fs::read(&path)?);
+                Ok((path, hash))
+            })
+            .collect()
+    }
+
+    /// Convert an individual blend file and parse the result with the `gltf` crate. The conversion
+    /// is written to a temporary directory that is removed afterwards.
+    ///
+    /// Only single file outputs are supported, so the output format must be
+    /// [`OutputFormat::Glb`] or [`OutputFormat::GltfEmbedded`] without a custom exporter,
+    /// [`ConversionOptions::per_object`], [`ConversionOptions::per_collection`] or
+    /// [`SceneSelection::All`].
+    #[cfg(feature = "gltf")]
+    pub fn convert_to_gltf(&self, input: &Path) -> Result<gltf::Gltf, Error> {
+        let single_file = matches!(
+            self.output_format,
+            OutputFormat::Glb | OutputFormat::GltfEmbedded
+        );
+        if !single_file
+            || self.exporter.is_some()
+            || self.per_object
+            || self.per_collection
+            || self.export_scenes == SceneSelection::All
+        {
+            return Err(Error::NotSingleFile);
+        }
+        let blender_exe = BlenderExecutable::find_using_options(self)?;
+        let temp_dir = unique_temp_path("gltf");
+        std::fs::create_dir_all(&temp_dir)?;
+        let output = temp_dir.join("output");
+        let result = self
+            .convert_internal(input, &output, &blender_exe)
+            .and_then(|_| {
+                let bytes = std::fs::read(self.active_exporter().exported_path(&output))?;
+                Ok(gltf::Gltf::from_slice(&bytes)?)
+            });
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        result
+    }
+
+    /// Check that a blend file opens in blender and contains exportable content (mesh, curve,
+    /// surface, metaball or text objects) without writing any output.
+    pub fn validate(&self, input: &Path) -> Result<(), Error> {
+        let blender_exe = BlenderExecutable::find_using_options(self)?;
+        self.validate_internal(input, &blender_exe)
+    }
+
+    /// Walks a directory and validates all the blend files, see [`ConversionOptions::validate`].
+    /// Failures are collected into the returned report rather than stopping the walk. Runs up to
+    /// [`ConversionOptions::jobs`] blenders at once, the report keeps the order of the walk.
+    pub fn validate_dir(&self, input_dir: &Path) -> Result<ConversionReport, Error> {
+        let blender_exe = BlenderExecutable::find_using_options(self)?;
+        let input_paths: Vec<PathBuf> = blend_files(input_dir).collect();
+        let mut results = Vec::with_capacity(input_paths.len());
+        run_jobs(
+            &input_paths,
+            self.jobs.unwrap_or(1).max(1),
+            true,
+            |input_path| self.validate_internal(input_path, &blender_exe),
+            |event| {
+                if let JobEvent::Finished(index, result, _) = event {
+                    results.push((index, result));
+                }
+            },
+        );
+        results.sort_by_key(|(index, _)| *index);
+        let mut report = ConversionReport::default();
+        for (index, result) in results {
+            let input_path = input_paths[index].clone();
+            match result {
+                Ok(()) => report.succeeded.push(input_path),
+                Err(err) => report.failed.push((input_path, err)),
+            }
+        }
+        Ok(report)
+    }
+
+    /// Estimates how expensive converting each blend in a directory will be, useful for
+    /// scheduling large batches. Only reads file sizes unless `deep` is true, in which case each
+    /// blend is also opened in blender to count objects and mesh vertices.
+    pub fn estimate(
+        &self,
+        input_dir: &Path,
+        deep: bool,
+    ) -> Result<Vec<(PathBuf, CostEstimate)>, Error> {
+        let blender_exe = if deep {
+            Some(BlenderExecutable::find_using_options(self)?)
+        } else {
+            None
+        };
+        blend_files(input_dir)
+            .map(|input_path| {
+                let mut estimate = CostEstimate {
+                    file_size: input_path.metadata()?.len(),
+                    ..Default::default()
+                };
+                if let Some(blender_exe) = &blender_exe {
+                    let (objects, vertices) = self.count_contents(&input_path, blender_exe)?;
+                    estimate.objects = Some(objects);
+                    estimate.vertices = Some(vertices);
+                }
+                Ok((input_path, estimate))
+            })
+            .collect()
+    }
+
+    /// Converts a blend and returns the paths of the files written. The files the export script
+    /// reported come first, followed by any other files that appeared, such as the .bin and
+    /// textures of [`OutputFormat::GltfSeparate`] whose names blender chooses.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            skip_all,
+            fields(input = %input.display(), output = %output.display())
+        )
+    )]
+    fn convert_internal(
+        &self,
+        input: &Path,
+        output: &Path,
+        blender_exe: &BlenderExecutable,
+    ) -> Result<Exported, Error> {
+        self.convert_with(input, output, |input_file_path, output, existing_output| {
+            self.export(input_file_path, output, existing_output, blender_exe)
+        })
+    }
+
+    /// [`ConversionOptions::convert_internal`] with `export` called to export the checked input
+    /// to an output path, given the path of any output from an earlier conversion
+    fn convert_with(
+        &self,
+        input: &Path,
+        output: &Path,
+        mut export: impl FnMut(&Path, &Path, &Path) -> Result<Exported, Error>,
+    ) -> Result<Exported, Error> {
+        let input_file_path = checked_input_path(input)?;
+        let exported_path = self.active_exporter().exported_path(output);
+        if exported_path.exists() {
+            match self.overwrite {
+                OverwritePolicy::Overwrite => {}
+                OverwritePolicy::Skip => return Ok(Exported::default()),
+                OverwritePolicy::Error => return Err(Error::OutputExists(exported_path)),
+            }
+        }
+        if self.atomic {
+            self.export_atomic(&input_file_path, output, export)
+        } else {
+            // Only the output's own directory is scanned, blender writes sidecars next to the
+            // main file unless told otherwise
+            let output_parent = output.parent().unwrap_or(Path::new(""));
+            let before = file_times(output_parent)?;
+            let exported = export(&input_file_path, output, output)?;
+            let written = file_times(output_parent)?
+                .into_iter()
+                .filter(|(path, modified)| before.get(path) != Some(modified))
+                .map(|(path, _)| path);
+            Ok(Exported {
+                outputs: merge_written(exported.outputs, written),
+                ..exported
+            })
+        }
+    }
+
+    fn export_atomic(
+        &self,
+        input_file_path: &Path,
+        output: &Path,
+        mut export: impl FnMut(&Path, &Path, &Path) -> Result<Exported, Error>,
+    ) -> Result<Exported, Error> {
+        let file_name = output
+            .file_name()
+            .ok_or(Error::InvalidOutputFile(output.to_path_buf()))?;
+        let output_parent = output.parent().unwrap_or(Path::new(""));
+        let staging_dir = output_parent.join(format!(
+            ".blend_converter-{}-{}",
+            std::process::id(),
+            file_name.to_string_lossy()
+        ));
+        std::fs::create_dir_all(&staging_dir)?;
+        let result =
+            export(input_file_path, &staging_dir.join(file_name), output).and_then(|staged| {
+                // Everything in the staging directory was written by this export
+                let written = move_dir_contents(&staging_dir, output_parent)?;
+                let reported = staged
+                    .outputs
+                    .iter()
+                    .map(|path| match path.strip_prefix(&staging_dir) {
+                        Ok(relative) => output_parent.join(relative),
+                        Err(_) => path.clone(),
+                    })
+                    .collect();
+                Ok(Exported {
+                    outputs: merge_written(reported, written),
+                    ..staged
+                })
+            });
+        // Best effort cleanup, the export result is more important than a stray directory
+        let _ = std::fs::remove_dir_all(&staging_dir);
+        result
+    }
+
+    fn export(
+        &self,
+        input_file_path: &Path,
+        output: &Path,
+        existing_output: &Path,
+        blender_exe: &BlenderExecutable,
+    ) -> Result<Exported, Error> {
+        let script = self.export_script(output, existing_output);
+        let output = self.run_blender(blender_exe, input_file_path, &script)?;
+        self.finish_export(input_file_path, &output)
+    }
+
+    /// Checks how the export script exited and post processes what it exported
+    fn finish_export(
+        &self,
+        input_file_path: &Path,
+        output: &BlenderOutput,
+    ) -> Result<Exported, Error> {
+        match output.status.code() {
+            Some(0) => {}
+            Some(EXPORT_MISSING_TEXTURES) => {
+                return Err(Error::MissingTextures(input_file_path.to_path_buf()))
+            }
+            Some(NOTHING_TO_EXPORT) => {
+                return Err(Error::NothingToExport(input_file_path.to_path_buf()))
+            }
+            Some(SCENE_NOT_FOUND) => {
+                return Err(Error::SceneNotFound(
+                    input_file_path.to_path_buf(),
+                    self.scene.clone().unwrap_or_default(),
+                ))
+            }
+            _ => return Err(output.export_error()),
+        }
+        let exported_paths: Vec<PathBuf> = output
+            .marked_lines(OUTPUT_MARKER)
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+        let lods: Vec<(String, Vec<String>)> = output
+            .marked_lines(LOD_MARKER)
+            .iter()
+            .filter_map(|line| {
+                let mut names: Vec<String> = serde_json::from_str(line).ok()?;
+                (!names.is_empty()).then(|| (names.remove(0), names))
+            })
+            .collect();
+        for exported_path in &exported_paths {
+            self.post_process_export(input_file_path, exported_path, &lods)?;
+        }
+        let sidecar_paths = output.marked_lines(SIDECAR_MARKER);
+        Ok(Exported {
+            outputs: exported_paths
+                .into_iter()
+                .chain(sidecar_paths.into_iter().map(PathBuf::from))
+                .collect(),
+            warnings: output.warnings(),
+            dependencies: output
+                .marked_lines(LIBRARY_MARKER)
+                .into_iter()
+                .map(PathBuf::from)
+                .collect(),
+            ngons: output
+                .marked_lines(NGONS_MARKER)
+                .iter()
+                .filter_map(|line| {
+                    let (count, name) = line.split_once(' ')?;
+                    Some((name.to_owned(), count.parse().ok()?))
+                })
+                .collect(),
+        })
+    }
+
+    /// Rewrites an exported file for the options that need it. `lods` are the levels of detail
+    /// generated for each object.
+    fn post_process_export(
+        &self,
+        input_file_path: &Path,
+        exported_path: &Path,
+        lods: &[(String, Vec<String>)],
+    ) -> Result<(), Error> {
+        // Custom exporters may not write glTF at all
+        if self.exporter.is_some() || !self.output_format.is_gltf() {
+            return Ok(());
+        }
+        if let Some(gltfpack) = &self.post_process.gltfpack {
+            postprocess::run_gltfpack(exported_path, gltfpack)?;
+        }
+        let embed_source = self.embed_source;
+        let add_lods = !lods.is_empty();
+        let root_transform = self.root_transform;
+        let ktx2 = self.output_format == OutputFormat::GltfSeparate
+            && self.gltf.image_format == GltfImageFormat::Ktx2;
+        if self.deterministic_ordering
+            || embed_source
+            || add_lods
+            || root_transform.is_some()
+            || ktx2
+        {
+            postprocess::edit_json(exported_path, |json| {
+                if ktx2 {
+                    let dir = exported_path.parent().unwrap_or(Path::new(""));
+                    postprocess::encode_ktx2(json, dir, Path::new("toktx"))?;
+                }
+                if add_lods {
+                    postprocess::add_msft_lod(json, lods);
+                }
+                if let Some(transform) = &root_transform {
+                    postprocess::wrap_roots(json, transform);
+                }
+                if self.deterministic_ordering {
+                    postprocess::sort_nodes_and_meshes(json);
+                }
+                if embed_source {
+                    let source = input_file_path.to_string_lossy();
+                    postprocess::set_asset_extra(json, "source_blend", source.as_ref().into());
+                    if let Some(version) = &self.source_version {
+                        postprocess::set_asset_extra(
+                            json,
+                            "source_version",
+                            version.as_str().into(),
+                        );
+                    }
+                }
+                Ok(())
+            })?;
+        }
+        #[cfg(feature = "gltf")]
+        if self.post_process.validate {
+            validate_gltf(exported_path)?;
+        }
+        Ok(())
+    }
+
+    fn validate_internal(
+        &self,
+        input: &Path,
+        blender_exe: &BlenderExecutable,
+    ) -> Result<(), Error> {
+        let input_file_path = checked_input_path(input)?;
+        // If the blend fails to load blender falls back to the startup file which has no filepath
+        let script = format!(
+            "import bpy, sys
+if not bpy.data.filepath:
+    sys.exit({VALIDATE_LOAD_FAILED})
+if not any(o.type in {{'MESH', 'CURVE', 'SURFACE', 'META', 'FONT'}} for o in bpy.data.objects):
+    sys.exit({NOTHING_TO_EXPORT})
+"
+        );
+        let output = self.run_blender(blender_exe, &input_file_path, &script)?;
+        match output.status.code() {
+            Some(0) => Ok(()),
+            Some(VALIDATE_LOAD_FAILED) => Err(Error::InvalidBlend(input_file_path)),
+            Some(NOTHING_TO_EXPORT) => Err(Error::NothingToExport(input_file_path)),
+            _ => Err(output.export_error()),
+        }
+    }
+
+    /// Opens a blend in blender and returns the number of objects and total mesh vertices
+    fn count_contents(
+        &self,
+        input: &Path,
+        blender_exe: &BlenderExecutable,
+    ) -> Result<(usize, usize), Error> {
+        let input_file_path = checked_input_path(input)?;
+        let script = format!(
+            "import bpy
+vertices = sum(len(o.data.vertices) for o in bpy.data.objects if o.type == 'MESH')
+print('{COUNT_MARKER}', len(bpy.data.objects), vertices)
+"
+        );
+        let output = self.run_blender(blender_exe, &input_file_path, &script)?;
+        if !output.status.success() {
+            return Err(output.export_error());
+        }
+        output
+            .marked_lines(COUNT_MARKER)
+            .iter()
+            .find_map(|line| {
+                let mut counts = line.split_whitespace();
+                Some((counts.next()?.parse().ok()?, counts.next()?.parse().ok()?))
+            })
+            .ok_or(Error::InvalidBlend(input_file_path))
+    }
+
+    /// Environment variables to set for blender
+    fn blender_env(&self) -> Result<Vec<(&'static str, OsString)>, Error> {
+        let mut env = Vec::new();
+        if let Some(dir) = &self.user_resources_dir {
+            std::fs::create_dir_all(dir)?;
+            // Use an absolute path so it does not depend on the working directory blender sees
+            let dir = dir.canonicalize()?;
+            env.push(("BLENDER_USER_RESOURCES", dir.clone().into_os_string()));
+            env.push(("BLENDER_USER_CONFIG", dir.join("config").into_os_string()));
+            env.push(("BLENDER_USER_SCRIPTS", dir.join("scripts").into_os_string()));
+            env.push((
+                "BLENDER_USER_DATAFILES",
+                dir.join("datafiles").into_os_string(),
+            ));
+        }
+        Ok(env)
+    }
+
+    /// Runs `script` in blender with `input_file_path` loaded. Blender's stdout and stderr are
+    /// passed through as they are written and also collected into the returned output.
+    fn run_blender(
+        &self,
+        blender_exe: &BlenderExecutable,
+        input_file_path: &Path,
+        script: &str,
+    ) -> Result<BlenderOutput, Error> {
+        let output = self.spawn_blender(blender_exe, Some(input_file_path), script)?;
+        if output.timed_out {
+            Err(Error::Timeout(input_file_path.to_path_buf()))
+        } else if self.is_out_of_memory(&output) {
+            Err(Error::OutOfMemory(input_file_path.to_path_buf()))
+        } else {
+            Ok(output)
+        }
+    }
+
+    /// Whether blender looks to have run out of memory, see [`Error::OutOfMemory`]
+    fn is_out_of_memory(&self, output: &BlenderOutput) -> bool {
+        let exceeded_limit =
+            self.memory_limit.is_some() && exceeded_memory_limit(&output.status, &output.stderr);
+        exceeded_limit || is_oom_kill(&output.status, &output.stderr)
+    }
+
+    /// Runs `script` in blender with `input_file_path` loaded, or blender's startup file if it is
+    /// None
+    fn spawn_blender(
+        &self,
+        blender_exe: &BlenderExecutable,
+        input_file_path: Option<&Path>,
+        script: &str,
+    ) -> Result<BlenderOutput, Error> {
+        let mut cmd = blender_exe.cmd_with_env(&self.blender_env()?);
+        #[cfg(target_os = "linux")]
+        if let Some(limit) = self.memory_limit {
+            limit_address_space(&mut cmd, limit);
+        }
+        let async_cancel = cancel::cancel_token();
+        let cancel: Vec<&CancellationToken> = [async_cancel.as_ref(), self.cancellation.as_ref()]
+            .into_iter()
+            .flatten()
+            .collect();
+        if cancel.iter().any(|token| token.is_cancelled()) {
+            return Err(Error::Cancelled);
+        }
+        #[cfg(unix)]
+        if self.timeout.is_some() || !cancel.is_empty() {
+            use std::os::unix::process::CommandExt;
+            cmd.process_group(0);
+        }
+        cmd.arg("-b");
+        if let Some(input_file_path) = input_file_path {
+            cmd.arg(input_file_path);
+        }
+        cmd.arg("--python-exit-code")
+            .arg("10")
+            .arg("--python-expr")
+            .arg(script)
+            .stderr(Stdio::piped());
+        #[cfg(feature = "tracing")]
+        tracing::debug!(command = ?cmd, "running blender");
+        #[cfg(feature = "pty")]
+        let (mut child, child_stdout) = spawn_with_stdout(cmd, self.pty)?;
+        #[cfg(not(feature = "pty"))]
+        let (mut child, child_stdout) = spawn_with_stdout(cmd, false)?;
+        let child_stderr = child.stderr.take().expect("stderr is piped");
+        let (waited, stdout, stderr) = std::thread::scope(|scope| {
+            let stdout = scope.spawn(|| tee(child_stdout, std::io::stdout()));
+            let stderr = scope.spawn(|| tee(child_stderr, std::io::stderr()));
+            let waited = wait_for_exit(&mut child, self.timeout, &cancel);
+            (
+                waited,
+                stdout.join().expect("stdout thread panicked"),
+                stderr.join().expect("stderr thread panicked"),
+            )
+        });
+        let (status, waited) = waited?;
+        if waited == Waited::Cancelled {
+            return Err(Error::Cancelled);
+        }
+        let output = BlenderOutput {
+            status,
+            stdout: stdout?,
+            stderr: stderr?,
+            timed_out: waited == Waited::TimedOut,
+        };
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            status = %output.status,
+            timed_out = output.timed_out,
+            stdout = %String::from_utf8_lossy(&output.stdout),
+            stderr = %String::from_utf8_lossy(&output.stderr),
+            "blender exited"
+        );
+        Ok(output)
+    }
+}
+
+/// Spawns `cmd` and returns the child with a reader for its stdout. When `pty` is set stdout is a
+/// pseudo terminal if one can be allocated, otherwise it is a pipe.
+fn spawn_with_stdout(
+    mut cmd: Command,
+    pty: bool,
+) -> std::io::Result<(std::process::Child, Box<dyn Read + Send>)> {
+    #[cfg(all(unix, feature = "pty"))]
+    if pty {
+        if let Ok(pty) = nix::pty::openpty(None, None) {
+            cmd.stdout(Stdio::from(pty.slave));
+            let child = cmd.spawn()?;
+            // Our copy of the slave must be closed to see end of file once blender exits
+            drop(cmd);
+            return Ok((child, Box::new(PtyReader(std::fs::File::from(pty.master)))));
+        }
+    }
+    #[cfg(not(all(unix, feature = "pty")))]
+    let _ = pty;
+    let mut child = cmd.stdout(Stdio::piped()).spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    Ok((child, Box::new(stdout)))
+}
+
+/// Why [`wait_for_exit`] stopped waiting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Waited {
+    Exited,
+    TimedOut,
+    Cancelled,
+}
+
+/// Waits for `child` to exit, killing it once `timeout` has passed or any of `cancel` is
+/// cancelled. Returns how it exited and why.
+fn wait_for_exit(
+    child: &mut std::process::Child,
+    timeout: Option<Duration>,
+    cancel: &[&CancellationToken],
+) -> std::io::Result<(ExitStatus, Waited)> {
+    if timeout.is_none() && cancel.is_empty() {
+        return Ok((child.wait()?, Waited::Exited));
+    }
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok((status, Waited::Exited));
+        }
+        let now = Instant::now();
+        let waited = if cancel.iter().any(|token| token.is_cancelled()) {
+            Waited::Cancelled
+        } else if deadline.is_some_and(|deadline| now >= deadline) {
+            Waited::TimedOut
+        } else {
+            let poll = Duration::from_millis(50);
+            std::thread::sleep(deadline.map_or(poll, |deadline| (deadline - now).min(poll)));
+            continue;
+        };
+        kill_process_tree(child)?;
+        return Ok((child.wait()?, waited));
+    }
+}
+
+/// Kills the process group `child` leads, see [`ConversionOptions::timeout`]
+#[cfg(unix)]
+fn kill_process_tree(child: &mut std::process::Child) -> std::io::Result<()> {
+    let pgid = libc::pid_t::try_from(child.id()).expect("pid fits in pid_t");
+    // SAFETY: kill has no memory safety requirements
+    if unsafe { libc::kill(-pgid, libc::SIGKILL) } == 0 {
+        Ok(())
+    } else {
+        child.kill()
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_process_tree(child: &mut std::process::Child) -> std::io::Result<()> {
+    child.kill()
+}
+
+/// Reads from a pseudo terminal master. Linux reports EIO instead of end of file once every
+/// slave is closed.
+#[cfg(all(unix, feature = "pty"))]
+struct PtyReader(std::fs::File);
+
+#[cfg(all(unix, feature = "pty"))]
+impl Read for PtyReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.0.read(buf) {
+            Err(err) if err.raw_os_error() == Some(nix::libc::EIO) => Ok(0),
+            result => result,
+        }
+    }
+}
+
+/// Copies `reader` into `writer` line by line as it is read and returns everything that was read
+fn tee(reader: impl Read, mut writer: impl Write) -> std::io::Result<Vec<u8>> {
+    let mut reader = BufReader::new(reader);
+    let mut collected = Vec::new();
+    loop {
+        let start = collected.len();
+        if reader.read_until(b'\n', &mut collected)? == 0 {
+            return Ok(collected);
+        }
+        writer.write_all(&collected[start..])?;
+    }
+}
+
+/// Parses the last python traceback in `stderr`. Blender reports calling an operator that does
+/// not exist, usually because blender is older than the exporter needs, as an `AttributeError`
+/// naming the operator.
+fn python_exception(stderr: &str) -> Option<Error> {
+    let start = stderr.rfind("Traceback (most recent call last):")?;
+    let lines: Vec<&str> = stderr[start..].lines().collect();
+    // The frames are indented so the first unindented line after the header is the exception
+    let end = lines
+        .iter()
+        .skip(1)
+        .position(|line| !line.starts_with(' '))?
+        + 1;
+    let exception = lines[end].trim().to_owned();
+    if let Some(operator) = exception
+        .strip_prefix("AttributeError: Calling operator \"")
+        .and_then(|rest| rest.strip_suffix("\" error, could not be found"))
+    {
+        return Some(Error::UnknownOperator(operator.to_owned()));
+    }
+    Some(Error::PythonException {
+        exception,
+        traceback: lines[..=end].join("\n"),
+    })
+}
+
+/// What a blender invocation printed and how it exited
+#[derive(Debug)]
+struct BlenderOutput {
+    status: ExitStatus,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    /// Whether blender was killed for running past [`ConversionOptions::timeout`]
+    timed_out: bool,
+}
+
+impl BlenderOutput {
+    /// Number of lines from the end of stdout and stderr kept in [`Error::Export`]
+    const TAIL_LINES: usize = 20;
+
+    /// The error for this failed invocation. When the script raised, which blender reports with
+    /// exit code 10, the last python traceback on stderr is parsed into
+    /// [`Error::UnknownOperator`] or [`Error::PythonException`], anything else is an
+    /// [`Error::Export`] with the end of stdout and stderr.
+    fn export_error(&self) -> Error {
+        let stderr = String::from_utf8_lossy(&self.stderr);
+        if self.status.code() == Some(10) {
+            if let Some(err) = python_exception(&stderr) {
+                return err;
+            }
+        }
+        let tail = |output: &str| {
+            let lines: Vec<&str> = output.trim_end().lines().collect();
+            lines[lines.len().saturating_sub(Self::TAIL_LINES)..].join("\n")
+        };
+        Error::Export {
+            status: self.status,
+            stdout: tail(&String::from_utf8_lossy(&self.stdout)),
+            stderr: tail(&stderr),
+        }
+    }
+
+    /// Splits the output of a batch into the output of each job that finished, in order. Each job
+    /// gets the stdout it printed, the exit code its script finished with and all of stderr.
+    fn batch_jobs(&self) -> Vec<BlenderOutput> {
+        let mut jobs = Vec::new();
+        let mut job_stdout = Vec::new();
+        for line in String::from_utf8_lossy(&self.stdout).lines() {
+            if line.starts_with(BATCH_START_MARKER) {
+                job_stdout.clear();
+            } else if let Some(rest) = line.strip_prefix(BATCH_END_MARKER) {
+                let code = rest
+                    .split_whitespace()
+                    .nth(1)
+                    .and_then(|code| code.parse().ok());
+                jobs.push(BlenderOutput {
+                    status: exit_status(code.unwrap_or(1)),
+                    stdout: std::mem::take(&mut job_stdout),
+                    stderr: self.stderr.clone(),
+                    timed_out: false,
+                });
+            } else {
+                job_stdout.extend_from_slice(line.as_bytes());
+                job_stdout.push(b'\n');
+            }
+        }
+        jobs
+    }
+
+    /// The messages of the warnings blender and its exporters printed, in the order they appear on
+    /// stdout then stderr
+    fn warnings(&self) -> Vec<String> {
+        [&self.stdout, &self.stderr]
+            .into_iter()
+            .flat_map(|output| {
+                String::from_utf8_lossy(output)
+                    .lines()
+                    .filter_map(warning_message)
+                    .map(str::to_owned)
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// The rest of each stdout line starting with `marker` and a space
+    fn marked_lines(&self, marker: &str) -> Vec<String> {
+        String::from_utf8_lossy(&self.stdout)
+            .lines()
+            .filter_map(|line| line.strip_prefix(marker)?.strip_prefix(' '))
+            .map(str::to_owned)
+            .collect()
+    }
+}
+
+/// The message of a warning line. Blender's reports start with `Warning:` and the glTF exporter
+/// logs `<time> | WARNING: <message>`.
+fn warning_message(line: &str) -> Option<&str> {
+    let line = line.trim();
+    let message = match line.split_once("| WARNING: ") {
+        Some((_, message)) => message,
+        None => line
+            .strip_prefix("Warning: ")
+            .or_else(|| line.strip_prefix("WARNING: "))?,
+    };
+    Some(message.trim())
+}
+
+/// Exit code used by the validation script when the blend failed to load
+const VALIDATE_LOAD_FAILED: i32 = 11;
+/// Exit code used by the validation and export scripts when the blend has nothing to export
+const NOTHING_TO_EXPORT: i32 = 12;
+
+/// Prefixes for the lines printed by the batch script around each job, followed by the job's
+/// index and for the end marker the job's exit code
+const BATCH_START_MARKER: &str = "BLEND_CONVERTER_BATCH_START";
+const BATCH_END_MARKER: &str = "BLEND_CONVERTER_BATCH_END";
+
+/// Exit code used by the export script when images are missing and textures are required
+const EXPORT_MISSING_TEXTURES: i32 = 13;
+
+/// Exit code used by the export script when [`ConversionOptions::scene`] is not in the blend
+const SCENE_NOT_FOUND: i32 = 14;
+
+/// Prefix for the line printed by the count script so it can be found amongst blender's output
+const COUNT_MARKER: &str = "BLEND_CONVERTER_COUNT";
+
+/// An exit status with `code`, for exit codes reported by scripts rather than processes
+#[cfg(unix)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+    ExitStatus::from_raw(code << 8)
+}
+
+#[cfg(windows)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::windows::process::ExitStatusExt;
+    ExitStatus::from_raw(code as u32)
+}
+
+/// A path in the temporary directory that no other conversion in any process is using
+fn unique_temp_path(name: &str) -> PathBuf {
+    static NEXT_ID: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    env::temp_dir().join(format!(
+        "blend_converter-{}-{id}-{name}",
+        std::process::id()
+    ))
+}
+
+/// Checks `input` has the .blend file extension and canonicalizes it. The check happens first so
+/// the error names the path as given.
+fn checked_input_path(input: &Path) -> Result<PathBuf, Error> {
+    if input.extension().is_none_or(|ext| ext != "blend") {
+        return Err(Error::InvalidInputFile(input.to_path_buf()));
+    }
+    Ok(input.canonicalize()?)
+}
+
+/// The key of a blend in the [`Cache`], its path relative to the converted directory
+fn cache_key(input_dir: &Path, input_path: &Path) -> String {
+    input_path
+        .strip_prefix(input_dir)
+        .unwrap_or(input_path)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Walks `input_dir` yielding every file with the .blend extension
+fn blend_files(input_dir: &Path) -> impl Iterator<Item = PathBuf> {
+    WalkDir::new(input_dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            // Ignore directories
+            entry.metadata().is_ok_and(|m| m.is_file())
+        })
+        // Ignore everything except blend files
+        .filter(|entry| entry.path().extension() == Some(OsStr::new("blend")))
+        .map(|entry| entry.into_path())
+}
+
+/// A rough estimate of how expensive a blend is to convert, see [`ConversionOptions::estimate`]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CostEstimate {
+    /// Size of the blend file in bytes
+    pub file_size: u64,
+    /// Number of objects in the blend, only set for deep estimates
+    pub objects: Option<usize>,
+    /// Total number of vertices across all mesh objects, only set for deep estimates
+    pub vertices: Option<usize>,
+}
+
+/// A blend converted by [`ConversionOptions::convert_dir`] or
+/// [`ConversionOptions::convert_detailed`]
+#[derive(Debug, Clone)]
+pub struct ConvertedBlend {
+    /// The blend that was converted
+    pub input: PathBuf,
+    /// Every file written for the blend, as returned by [`ConversionOptions::convert`]
+    pub outputs: Vec<PathBuf>,
+    /// The size in bytes of each of [`ConvertedBlend::outputs`], 0 for files that were removed
+    /// after being written
+    pub sizes: Vec<u64>,
+    /// How long the conversion took. With [`ConversionOptions::batch`] this is from the start of
+    /// the batch.
+    pub duration: Duration,
+    /// Warnings blender and its exporters printed while converting the blend. With
+    /// [`ConversionOptions::batch`] the warnings blender printed to stderr are included for
+    /// every blend in the batch.
+    pub warnings: Vec<String>,
+    /// The totals for every glTF file written, set when [`PostProcess::stats`] is
+    pub stats: Option<GltfStats>,
+    /// The library blends the blend links data from, including libraries linked by those
+    /// libraries. Changes to them change the export too.
+    pub dependencies: Vec<PathBuf>,
+    /// The name of each mesh containing n-gons and how many it has, found when
+    /// [`ConversionOptions::warn_on_ngons`] is set. Empty otherwise.
+    pub ngons: Vec<(String, usize)>,
+}
+
+impl ConvertedBlend {
+    fn new(
+        options: &ConversionOptions,
+        input: PathBuf,
+        exported: Exported,
+        duration: Duration,
+    ) -> Self {
+        let sizes = exported
+            .outputs
+            .iter()
+            .map(|path| path.metadata().map_or(0, |metadata| metadata.len()))
+            .collect();
+        let stats = options.post_process.stats.then(|| {
+            let mut stats = GltfStats::default();
+            let is_gltf = |path: &&PathBuf| {
+                path.extension()
+                    .is_some_and(|ext| ext == "gltf" || ext == "glb")
+            };
+            for path in exported.outputs.iter().filter(is_gltf) {
+                // Outputs that are not valid glTF are caught by validation
+                if let Ok(json) = postprocess::read_json(path) {
+                    stats += postprocess::stats(&json, path.parent().unwrap_or(Path::new("")));
+                }
+            }
+            stats
+        });
+        Self {
+            input,
+            outputs: exported.outputs,
+            sizes,
+            duration,
+            warnings: exported.warnings,
+            stats,
+            dependencies: exported.dependencies,
+            ngons: exported.ngons,
+        }
+    }
+
+    /// The total size in bytes of the written files
+    pub fn total_size(&self) -> u64 {
+        self.sizes.iter().sum()
+    }
+}
+
+/// Every blend processed by [`ConversionOptions::convert_dir_summary`]
+#[derive(Debug, Clone, Default)]
+pub struct DirConversionSummary {
+    /// Blends that were converted, in the order of the directory walk
+    pub converted: Vec<ConvertedBlend>,
+    /// Blends that were not converted as their outputs are up to date
+    pub skipped: Vec<PathBuf>,
+    /// How long converting the directory took
+    pub duration: Duration,
+}
+
+impl DirConversionSummary {
+    /// The total size in bytes of every file written
+    pub fn total_size(&self) -> u64 {
+        self.converted.iter().map(ConvertedBlend::total_size).sum()
+    }
+
+    /// Every warning printed while converting, with the blend it was printed for
+    pub fn warnings(&self) -> impl Iterator<Item = (&Path, &str)> {
+        self.converted.iter().flat_map(|blend| {
+            blend
+                .warnings
+                .iter()
+                .map(|warning| (blend.input.as_path(), warning.as_str()))
+        })
+    }
+}
+
+/// Progress of [`ConversionOptions::convert_dir_with_progress`]. [`ConversionEvent::Total`] is
+/// sent first, then every blend gets a [`ConversionEvent::FileStarted`] followed by exactly one
+/// of the other events, unless the conversion stops after a failure before reaching it.
+#[derive(Debug, Clone, Copy)]
+pub enum ConversionEvent<'a> {
+    /// The number of blends found in the input directory
+    Total(usize),
+    /// The blend is about to be converted or checked for being up to date. With
+    /// [`ConversionOptions::jobs`] several blends can be started at once and with
+    /// [`ConversionOptions::batch`] every blend is started before blender runs.
+    FileStarted {
+        /// The blend
+        path: &'a Path,
+    },
+    /// The blend was converted
+    FileFinished {
+        /// The blend
+        path: &'a Path,
+        /// Every file written for the blend
+        outputs: &'a [PathBuf],
+        /// How long the conversion took. With [`ConversionOptions::batch`] this is from the start
+        /// of the batch.
+        duration: Duration,
+    },
+    /// The blend was not converted as its outputs are up to date
+    FileSkipped {
+        /// The blend
+        path: &'a Path,
+    },
+    /// The blend failed to convert
+    FileFailed {
+        /// The blend
+        path: &'a Path,
+        /// Why it failed
+        error: &'a Error,
+    },
+}
+
+/// Logs each event with the `tracing` feature before passing it to `on_event`
+fn traced(mut on_event: impl FnMut(ConversionEvent<'_>)) -> impl FnMut(ConversionEvent<'_>) {
+    move |event| {
+        #[cfg(feature = "tracing")]
+        match event {
+            ConversionEvent::Total(count) => tracing::info!(count, "found blends"),
+            ConversionEvent::FileStarted { path } => {
+                tracing::debug!(path = %path.display(), "starting blend");
+            }
+            ConversionEvent::FileFinished {
+                path,
+                outputs,
+                duration,
+            } => tracing::info!(path = %path.display(), ?outputs, ?duration, "converted blend"),
+            ConversionEvent::FileSkipped { path } => {
+                tracing::info!(path = %path.display(), "blend is up to date");
+            }
+            ConversionEvent::FileFailed { path, error } => {
+                tracing::warn!(path = %path.display(), %error, "failed to convert blend");
+            }
+        }
+        on_event(event);
+    }
+}
+
+/// Adapts an `on_error` callback to [`ConversionEvent`]s
+fn failed_only(mut on_error: impl FnMut(&Path, &Error)) -> impl FnMut(ConversionEvent<'_>) {
+    move |event| {
+        if let ConversionEvent::FileFailed { path, error } = event {
+            on_error(path, error);
+        }
+    }
+}
+
+/// The outcome of processing every blend in a directory
+#[derive(Debug, Default)]
+pub struct ConversionReport {
+    /// Blends that were processed successfully
+    pub succeeded: Vec<PathBuf>,
+    /// Blends that failed along with the reason they failed
+    pub failed: Vec<(PathBuf, Error)>,
+}
+
+impl ConversionReport {
+    /// Returns true if no blends failed
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// What [`ConversionOptions::convert_dir`] would do with a directory, see
+/// [`ConversionOptions::plan_dir`]
+#[derive(Debug, Clone)]
+pub struct ConversionPlan {
+    options: ConversionOptions,
+    input_dir: PathBuf,
+    output_dir: PathBuf,
+    /// Every file found in the input directory, in the order of the directory walk
+    pub files: Vec<PlannedFile>,
+}
+
+impl ConversionPlan {
+    /// The blends that would be converted
+    pub fn to_convert(&self) -> impl Iterator<Item = &PlannedFile> {
+        self.files.iter().filter(|file| file.skip.is_none())
+    }
+
+    /// The files that would not be converted, each with the reason why
+    pub fn skipped(&self) -> impl Iterator<Item = (&PlannedFile, SkipReason)> {
+        self.files
+            .iter()
+            .filter_map(|file| Some((file, file.skip?)))
+    }
+
+    /// Converts the blends the plan would convert, like [`ConversionOptions::convert_dir`]. Blends
+    /// that changed since planning are not checked again.
+    pub fn execute(&self) -> Result<Vec<ConvertedBlend>, Error> {
+        let to_convert: Vec<PathBuf> = self.to_convert().map(|file| file.input.clone()).collect();
+        let options = ConversionOptions {
+            force: true,
+            ..self.options.clone()
+        };
+        let (converted, failed) = if to_convert.is_empty() {
+            (Vec::new(), Vec::new())
+        } else {
+            options.convert_paths(
+                to_convert,
+                &self.input_dir,
+                &self.output_dir,
+                false,
+                traced(|_| {}),
+            )?
+        };
+        if self.options.incremental {
+            let mut cache = Cache::load(&self.output_dir, &self.options);
+            let mut keys = Vec::new();
+            for file in &self.files {
+                let Some(hash) = &file.hash else {
+                    continue;
+                };
+                let key = cache_key(&self.input_dir, &file.input);
+                if let Some(blend) = converted.iter().find(|blend| blend.input == file.input) {
+                    cache.insert(
+                        key.clone(),
+                        hash,
+                        &file.output,
+                        &blend.outputs,
+                        &blend.dependencies,
+                    );
+                }
+                keys.push(key);
+            }
+            cache.retain(&keys);
+            cache.save()?;
+        }
+        match failed.into_iter().next() {
+            Some((_, err)) => Err(err),
+            None => Ok(converted),
+        }
+    }
+}
+
+/// A file found by [`ConversionOptions::plan_dir`]
+#[derive(Debug, Clone)]
+pub struct PlannedFile {
+    /// The file in the input directory
+    pub input: PathBuf,
+    /// The main file the blend would be exported to, empty for files that are not blends
+    pub output: PathBuf,
+    /// Why the file would not be converted, None if it would be
+    pub skip: Option<SkipReason>,
+    /// The blend's content hash for [`ConversionOptions::incremental`]
+    hash: Option<String>,
+}
+
+/// Why [`ConversionOptions::plan_dir`] would not convert a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The file does not have the .blend extension
+    NotBlend,
+    /// The export is newer than the blend, see [`ConversionOptions::force`]
+    UpToDate,
+    /// The blend is unchanged since it was last converted, see
+    /// [`ConversionOptions::incremental`]
+    Cached,
+    /// The export exists and [`OverwritePolicy::Skip`] is set
+    OutputExists,
+}
+
+/// Moves every file in `from` into `to` preserving relative paths and returns the moved files'
+/// new paths. Falls back to copy and remove when `from` and `to` are on different filesystems.
+fn move_dir_contents(from: &Path, to: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut moved = Vec::new();
+    for entry in WalkDir::new(from).min_depth(1) {
+        let entry = entry.map_err(std::io::Error::from)?;
+        let relative = entry
+            .path()
+            .strip_prefix(from)
+            .expect("walkdir entries are inside the root");
+        let destination = to.join(relative);
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&destination)?;
+            continue;
+        }
+        match std::fs::rename(entry.path(), &destination) {
+            Err(err) if err.kind() == std::io::ErrorKind::CrossesDevices => {
+                std::fs::copy(entry.path(), &destination)?;
+                std::fs::remove_file(entry.path())?;
+            }
+            result => result?,
+        }
+        moved.push(destination);
+    }
+    Ok(moved)
+}
+
+/// The modification time of each file directly inside `dir`, with paths joined onto `dir` as
+/// given. An empty `dir` is the current directory.
+fn file_times(dir: &Path) -> Result<HashMap<PathBuf, SystemTime>, Error> {
+    let read_dir = if dir == Path::new("") {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let mut times = HashMap::new();
+    let entries = match std::fs::read_dir(read_dir) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(times),
+        result => result?,
+    };
+    for entry in entries {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_file() {
+            times.insert(dir.join(entry.file_name()), metadata.modified()?);
+        }
+    }
+    Ok(times)
+}
+
+/// Appends the `written` paths that are not already `reported`, sorted so the result does not
+/// depend on directory iteration order
+fn merge_written(
+    mut reported: Vec<PathBuf>,
+    written: impl IntoIterator<Item = PathBuf>,
+) -> Vec<PathBuf> {
+    let mut extra: Vec<PathBuf> = written
+        .into_iter()
+        .filter(|path| !reported.contains(path))
+        .collect();
+    extra.sort();
+    reported.extend(extra);
+    reported
+}
+
+/// Checks the glTF at `path` with the `gltf` crate, see [`PostProcess::validate`]
+#[cfg(feature = "gltf")]
+fn validate_gltf(path: &Path) -> Result<(), Error> {
+    let invalid = |source| Error::GltfValidation {
+        path: path.to_path_buf(),
+        source,
+    };
+    let gltf::Gltf { document, blob } = gltf::Gltf::open(path).map_err(invalid)?;
+    gltf::import_buffers(&document, path.parent(), blob).map_err(invalid)?;
+    Ok(())
+}
+
+/// Formats stderr for appending to an error message
+fn stderr_suffix(stderr: &str) -> String {
+    if stderr.is_empty() {
+        String::new()
+    } else {
+        format!(", stderr:\n{stderr}")
+    }
+}
+
+/// The OOM killer sends SIGKILL which gives blender no chance to print anything, so a SIGKILL with
+/// nothing on stderr is almost certainly an out of memory kill.
+#[cfg(unix)]
+fn is_oom_kill(status: &ExitStatus, stderr: &[u8]) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+    const SIGKILL: i32 = 9;
+    status.signal() == Some(SIGKILL) && stderr.iter().all(u8::is_ascii_whitespace)
+}
+
+#[cfg(not(unix))]
+fn is_oom_kill(_status: &ExitStatus, _stderr: &[u8]) -> bool {
+    false
+}
+
+/// Hex encoded SHA-256 hash of `bytes`
+#[cfg(feature = "sha2")]
+fn sha256_hex(bytes: &[u8]) -> String {
+    use sha2::Digest;
+    sha2::Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Sets `RLIMIT_AS` for the spawned process before it execs blender
+#[cfg(target_os = "linux")]
+fn limit_address_space(cmd: &mut Command, limit: u64) {
+    use std::os::unix::process::CommandExt;
+    let limit = libc::rlimit {
+        rlim_cur: limit as libc::rlim_t,
+        rlim_max: limit as libc::rlim_t,
+    };
+    // SAFETY: setrlimit is async signal safe and the closure does not allocate
+    unsafe {
+        cmd.pre_exec(move || {
+            if libc::setrlimit(libc::RLIMIT_AS, &limit) == 0 {
+                Ok(())
+            } else {
+                Err(std::io::Error::last_os_error())
+            }
+        });
+    }
+}
+
+/// Blender does not handle failed allocations, under an address space limit it either reports
+/// the failure and exits or crashes. So any crash, or an allocation failure on stderr, is taken
+/// to mean the limit was hit.
+#[cfg(target_os = "linux")]
+fn exceeded_memory_limit(status: &ExitStatus, stderr: &[u8]) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+    const ALLOCATION_FAILURES: [&str; 4] = [
+        "Malloc returns null",
+        "Calloc returns null",
+        "std::bad_alloc",
+        "MemoryError",
+    ];
+    let stderr = String::from_utf8_lossy(stderr);
+    !status.success()
+        && (status.signal().is_some()
+            || ALLOCATION_FAILURES
+                .iter()
+                .any(|failure| stderr.contains(failure)))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn exceeded_memory_limit(_status: &ExitStatus, _stderr: &[u8]) -> bool {
+    false
+}
+
+/// The blender executable search strategy
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum BlenderExecutable {
+    /// Invokes blender using `blender` because blender is in the path environment variable
+    #[default]
+    Normal,
+    /// Invokes blender using `flatpak run org.blender.Blender`
+    Flatpak,
+    /// Invokes blender using path provided by [`ConversionOptions::blender_path`]
+    Path(PathBuf),
+    /// Invokes `blender` inside a container of `image` with `docker run`, for machines without
+    /// blender installed. Only set through [`ConversionOptions::blender_executable`], it is never
+    /// found by searching.
+    ///
+    /// The current directory and each of `mounts` are mounted at the same path in the container,
+    /// so the blends, outputs and any other paths in the options must be inside one of them. On
+    /// unix the container runs as the current user so the outputs are not owned by root. When a
+    /// conversion times out or is cancelled the `docker` client is killed, which leaves stopping
+    /// the container to docker.
+    Docker {
+        /// The image, which must have `blender` in its path
+        image: String,
+        /// Directories mounted into the container besides the current directory
+        #[cfg_attr(feature = "serde", serde(default))]
+        mounts: Vec<PathBuf>,
+    },
+}
+
+impl BlenderExecutable {
+    fn find_using_options(options: &ConversionOptions) -> Result<Self, Error> {
+        let blender_exe = if let Some(executable) = &options.blender_executable {
+            if !matches!(executable.test(), Ok(true)) {
+                return Err(Error::MissingBlenderExecutable);
+            }
+            executable.clone()
+        } else if let Some(path) = &options.blender_path {
+            BlenderExecutable::find_using_path(path)?
+        } else {
+            BlenderExecutable::find()?
+        };
+        #[cfg(feature = "tracing")]
+        tracing::info!(executable = ?blender_exe, "using blender");
+        // Only asking blender its version when needed saves starting it an extra time
+        if let Some(required) = options.required_version() {
+            let found = blender_exe.version()?;
+            if found < required {
+                return Err(Error::UnsupportedVersion { found, required });
+            }
+        }
+        Ok(blender_exe)
+    }
+
+    /// The `(major, minor, patch)` version of blender, parsed from the `Blender X.Y.Z` line
+    /// printed by `blender -v`
+    pub fn version(&self) -> Result<(u32, u32, u32), Error> {
+        let output = self.cmd().arg("-b").arg("-v").output()?;
+        if !output.status.success() {
+            return Err(Error::MissingBlenderExecutable);
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        parse_version(&stdout)
+            // Some builds print the version after startup messages on stderr
+            .or_else(|| parse_version(&String::from_utf8_lossy(&output.stderr)))
+            .ok_or_else(|| Error::UnknownVersion(stdout.trim().to_owned()))
+    }
+
+    /// Find tries [`BlenderExecutable::Normal`] then [`BlenderExecutable::Flatpak`] and returns
+    /// the first one that succeeds otherwise returns [`Error::MissingBlenderExecutable`]
+    ///
+    /// Between the two the platform's usual install locations are tried as
+    /// [`BlenderExecutable::Path`]:
+    /// - On Windows the highest version installed under `Program Files\Blender Foundation`, in
+    ///   Steam's `steamapps\common\Blender` or in a directory the installer recorded in the
+    ///   registry.
+    /// - On macOS `Blender.app` in `/Applications` or `~/Applications`, the Steam install, then
+    ///   any other copy of the app Spotlight knows of.
+    /// - On Linux the snap and the Steam install.
+    pub fn find() -> Result<Self, Error> {
+        let mut candidates = vec![Self::Normal];
+        #[cfg(target_os = "windows")]
+        candidates.extend(find_installed(&windows_install_dirs()).map(Self::Path));
+        #[cfg(not(target_os = "windows"))]
+        candidates.extend(
+            installed_paths()
+                .into_iter()
+                .filter(|path| path.is_file())
+                .map(Self::Path),
+        );
+        candidates.push(Self::Flatpak);
+        candidates
+            .into_iter()
+            .find(|x| matches!(x.test(), Ok(true)))
+            .ok_or(Error::MissingBlenderExecutable)
+    }
+
+    /// Every installation [`BlenderExecutable::find`] would try that runs, with its version, in
+    /// the order they would be tried. Blender in `PATH` is left out when it is one of the
+    /// installations found at their usual locations.
+    pub fn discover_all() -> Vec<BlenderInstallation> {
+        let canonical = |path: &Path| std::fs::canonicalize(path).unwrap_or(path.to_path_buf());
+        let mut seen = std::collections::HashSet::new();
+        let mut candidates = Vec::new();
+        if let Some(path) = path_blender() {
+            seen.insert(canonical(&path));
+            candidates.push(Self::Normal);
+        }
+        for path in installed_paths() {
+            if path.is_file() && seen.insert(canonical(&path)) {
+                candidates.push(Self::Path(path));
+            }
+        }
+        candidates.push(Self::Flatpak);
+        candidates
+            .into_iter()
+            .filter_map(|executable| {
+                Some(BlenderInstallation {
+                    version: executable.version().ok()?,
+                    executable,
+                })
+            })
+            .collect()
+    }
+
+    /// Only tries `path` as the blender executable and if it succeeds returns
+    /// [`BlenderExecutable::Path`] with the `path` otherwise returns
+    /// [`Error::MissingBlenderExecutable`]
+    pub fn find_using_path(path: &Path) -> Result<Self, Error> {
+        let s = Self::Path(path.to_path_buf());
+        if matches!(s.test(), Ok(true)) {
+            Ok(s)
+        } else {
+            Err(Error::MissingBlenderExecutable)
+        }
+    }
+
+    /// Same as [`BlenderExecutable::cmd`] with extra environment variables set for blender. For
+    /// flatpak and docker these have to be passed through the sandbox with `--env`.
+    fn cmd_with_env(&self, env: &[(&str, OsString)]) -> Command {
+        if let Self::Docker { image, mounts } = self {
+            return docker_command(image, mounts, env);
+        }
+        if let Self::Flatpak = self {
+            let mut command = Command::new("flatpak");
+            command.arg("run");
+            for (key, value) in env {
+                let mut arg = OsString::from(format!("--env={key}="));
+                arg.push(value);
+                command.arg(arg);
+            }
+            command.arg("org.blender.Blender");
+            return command;
+        }
+        let mut command = self.cmd();
+        command.envs(env.iter().map(|(key, value)| (key, value)));
+        command
+    }
+
+    fn cmd(&self) -> Command {
+        match self {
+            Self::Normal => Command::new("blender"),
+            Self::Flatpak => {
+                let mut command = Command::new("flatpak");
+                command.arg("run").arg("org.blender.Blender");
+                command
+            }
+            Self::Path(path) => Command::new(path),
+            Self::Docker { image, mounts } => docker_command(image, mounts, &[]),
+        }
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", ret, err(level = "debug"))
+    )]
+    fn test(&self) -> std::io::Result<bool> {
+        Ok(self.cmd().arg("-b").arg("-v").status()?.success())
+    }
+}
+
+/// `docker run` for [`BlenderExecutable::Docker`], up to the arguments passed to blender
+fn docker_command(image: &str, mounts: &[PathBuf], env: &[(&str, OsString)]) -> Command {
+    let mut command = Command::new("docker");
+    // Stdin stays open for blender sessions, which send their jobs through it
+    command.arg("run").arg("--rm").arg("-i");
+    #[cfg(unix)]
+    {
+        // SAFETY: getuid and getgid always succeed and have no memory safety requirements
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        command.arg("--user").arg(format!("{uid}:{gid}"));
+    }
+    let current_dir = env::current_dir().ok();
+    for dir in current_dir.iter().chain(mounts) {
+        let dir = std::fs::canonicalize(dir).unwrap_or_else(|_| dir.clone());
+        let mut volume = dir.clone().into_os_string();
+        volume.push(":");
+        volume.push(&dir);
+        command.arg("--volume").arg(volume);
+    }
+    if let Some(dir) = &current_dir {
+        command.arg("--workdir").arg(dir);
+    }
+    for (key, value) in env {
+        let mut arg = OsString::from(format!("{key}="));
+        arg.push(value);
+        command.arg("--env").arg(arg);
+    }
+    command.arg(image).arg("blender");
+    command
+}
+
+/// A blender installation found by [`BlenderExecutable::discover_all`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlenderInstallation {
+    /// How to run the installation
+    pub executable: BlenderExecutable,
+    /// The `(major, minor, patch)` version, see [`BlenderExecutable::version`]
+    pub version: (u32, u32, u32),
+}
+
+/// The `blender` found in `PATH`, which [`BlenderExecutable::Normal`] runs
+fn path_blender() -> Option<PathBuf> {
+    let name = if cfg!(target_os = "windows") {
+        "blender.exe"
+    } else {
+        "blender"
+    };
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// The executables of the usual blender installs on this platform, whether or not they exist.
+/// See [`BlenderExecutable::find`] for the locations.
+fn installed_paths() -> Vec<PathBuf> {
+    #[allow(unused_mut)]
+    let mut paths = Vec::new();
+    #[cfg(target_os = "windows")]
+    paths.extend(
+        windows_install_dirs()
+            .into_iter()
+            .map(|dir| dir.join("blender.exe")),
+    );
+    #[cfg(target_os = "macos")]
+    {
+        let mut apps = vec![PathBuf::from("/Applications/Blender.app")];
+        if let Some(home) = env::var_os("HOME").map(PathBuf::from) {
+            apps.push(home.join("Applications/Blender.app"));
+            apps.push(
+                home.join("Library/Application Support/Steam/steamapps/common/Blender/Blender.app"),
+            );
+        }
+        // Spotlight also finds renamed copies such as `Blender 3.6.app`
+        if let Ok(output) = Command::new("mdfind")
+            .arg("kMDItemCFBundleIdentifier == 'org.blenderfoundation.blender'")
+            .output()
+        {
+            apps.extend(
+                String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .map(PathBuf::from),
+            );
+        }
+        paths.extend(
+            apps.into_iter()
+                .map(|app| app.join("Contents/MacOS/Blender")),
+        );
+    }
+    #[cfg(target_os = "linux")]
+    {
+        paths.push(PathBuf::from("/snap/bin/blender"));
+        if let Some(home) = env::var_os("HOME").map(PathBuf::from) {
+            for steam in [".steam/steam", ".local/share/Steam"] {
+                paths.push(home.join(steam).join("steamapps/common/Blender/blender"));
+            }
+        }
+    }
+    paths
+}
+
+/// The directories blender's Windows installer and Steam install into
+#[cfg(target_os = "windows")]
+fn windows_install_dirs() -> Vec<PathBuf> {
+    let program_files = env::var_os("ProgramFiles").unwrap_or_else(|| "C:\\Program Files".into());
+    let program_files_x86 =
+        env::var_os("ProgramFiles(x86)").unwrap_or_else(|| "C:\\Program Files (x86)".into());
+    let mut dirs: Vec<PathBuf> =
+        std::fs::read_dir(Path::new(&program_files).join("Blender Foundation"))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .collect();
+    dirs.push(Path::new(&program_files_x86).join("Steam\\steamapps\\common\\Blender"));
+    // Installs outside Program Files are only known from the registry
+    if let Ok(output) = Command::new("reg")
+        .args([
+            "query",
+            "HKLM\\SOFTWARE\\BlenderFoundation",
+            "/s",
+            "/v",
+            "Install_Dir",
+        ])
+        .output()
+    {
+        dirs.extend(reg_string_values(&String::from_utf8_lossy(&output.stdout)));
+    }
+    dirs
+}
+
+/// The data of the `REG_SZ` values listed by `reg query`, which prints each value as
+/// `<name>    REG_SZ    <data>` under the key it belongs to
+#[cfg(any(target_os = "windows", test))]
+fn reg_string_values(output: &str) -> Vec<PathBuf> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (_, data) = line.split_once("    REG_SZ    ")?;
+            Some(PathBuf::from(data.trim()))
+        })
+        .collect()
+}
+
+/// The `blender.exe` with the highest version among `dirs`. An install's version is taken from a
+/// `Blender X.Y` directory name, or else from the `X.Y` directory of bundled scripts every
+/// install has, which is all a Steam install has to go by.
+#[cfg(any(target_os = "windows", test))]
+fn find_installed(dirs: &[PathBuf]) -> Option<PathBuf> {
+    let major_minor = |name: &str| {
+        let (major, minor) = name.split_once('.')?;
+        Some((major.parse::<u32>().ok()?, minor.parse::<u32>().ok()?))
+    };
+    let file_name = |path: &Path| path.file_name()?.to_str().map(str::to_owned);
+    dirs.iter()
+        .filter(|dir| dir.join("blender.exe").is_file())
+        .filter_map(|dir| {
+            let version = file_name(dir)
+                .and_then(|name| major_minor(name.strip_prefix("Blender ")?))
+                .or_else(|| {
+                    std::fs::read_dir(dir)
+                        .ok()?
+                        .filter_map(|entry| major_minor(&file_name(&entry.ok()?.path())?))
+                        .max()
+                })?;
+            Some((version, dir.join("blender.exe")))
+        })
+        .max_by_key(|(version, _)| *version)
+        .map(|(_, exe)| exe)
+}
+
+/// Finds the first `Blender X.Y.Z` line in `output`. Flatpak prints its own messages before
+/// blender's, release builds add a suffix such as `LTS` or a build hash and older versions leave
+/// out the patch version or end it with a letter like `2.79b`.
+fn parse_version(output: &str) -> Option<(u32, u32, u32)> {
+    let leading_number = |part: &str| {
+        let digits = part
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(part.len());
+        part[..digits].parse().ok()
+    };
+    output.lines().find_map(|line| {
+        let version = line
+            .trim()
+            .strip_prefix("Blender ")?
+            .split_whitespace()
+            .next()?;
+        let mut parts = version.split('.');
+        let major = leading_number(parts.next()?)?;
+        let minor = leading_number(parts.next()?)?;
+        let patch = parts.next().map_or(Some(0), leading_number)?;
+        Some((major, minor, patch))
+    })
+}
+
+/// Errors for converting blends
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Could not locate blender executable, see [`BlenderExecutable`] for the search strategy
+    #[error("could not locate blender executable, is blender in your path?")]
+    MissingBlenderExecutable,
+    /// The blender executable is older than [`ConversionOptions::min_version`]
+    #[error("blender {}.{}.{} is older than the required {}.{}.{}", .found.0, .found.1, .found.2, .required.0, .required.1, .required.2)]
+    UnsupportedVersion {
+        /// The version of the blender executable
+        found: (u32, u32, u32),
+        /// The minimum version that was required
+        required: (u32, u32, u32),
+    },
+    /// No version could be found in the output of `blender -v`, which is included
+    #[error("could not find the blender version in {0:?}")]
+    UnknownVersion(String),
+    /// Invalid input file blend. This error occurs when the file extension is not .blend
+    #[error("invalid input path {0:?}, path must have .blend file extension")]
+    InvalidInputFile(PathBuf),
+    /// Invalid output path. This error occurs when the output path has no file name
+    #[error("invalid output path {0:?}, path must have a file name")]
+    InvalidOutputFile(PathBuf),
+    /// Blender could not load the blend file
+    #[error("blender could not load {0:?}")]
+    InvalidBlend(PathBuf),
+    /// The blend file has no objects that would produce exported geometry
+    #[error("{0:?} has nothing to export")]
+    NothingToExport(PathBuf),
+    /// Images used by the blend could not be found and [`ConversionOptions::require_textures`] is
+    /// set. The missing images are listed in blender's stderr.
+    #[error("{0:?} references missing image files")]
+    MissingTextures(PathBuf),
+    /// Blender exited unsuccessfully for a reason not covered by a more specific error.
+    /// `stdout` and `stderr` have the last lines blender printed to each.
+    #[error("export failed with {status}{}", stderr_suffix(.stderr))]
+    Export {
+        /// How blender exited
+        status: ExitStatus,
+        /// The end of blender's stdout
+        stdout: String,
+        /// The end of blender's stderr
+        stderr: String,
+    },
+    /// The conversion script raised a python exception inside blender
+    #[error("blender raised {exception}\n{traceback}")]
+    PythonException {
+        /// The exception's type and message, such as `RuntimeError: Error: no mesh`
+        exception: String,
+        /// The full traceback ending with the exception
+        traceback: String,
+    },
+    /// The conversion called a blender operator that does not exist, most likely because the
+    /// blender is too old for the output format or an option. The operator is included.
+    #[error("blender has no operator {0}, it may be too old for the options used")]
+    UnknownOperator(String),
+    /// An exported image could not be encoded for [`GltfImageFormat::Ktx2`]
+    #[error("failed to encode {image:?} as KTX2{}", stderr_suffix(.stderr))]
+    TextureEncoding {
+        /// The image that was being encoded
+        image: PathBuf,
+        /// What the encoder printed to stderr
+        stderr: String,
+    },
+    /// The export already exists and [`OverwritePolicy::Error`] is set
+    #[error("{0:?} already exists")]
+    OutputExists(PathBuf),
+    /// Two blends in a directory would be exported to the same path, see
+    /// [`ConversionOptions::output_layout`]
+    #[error("{first:?} and {second:?} would both be exported to {output:?}")]
+    OutputCollision {
+        /// The output path, without extension
+        output: PathBuf,
+        /// The first blend
+        first: PathBuf,
+        /// The second blend
+        second: PathBuf,
+    },
+    /// The blend has no scene named [`ConversionOptions::scene`]
+    #[error("{0:?} has no scene named {1:?}")]
+    SceneNotFound(PathBuf, String),
+    /// Blender was killed with no output while converting, most likely by the out of memory
+    /// killer. Try converting on a machine with more memory or splitting the blend into smaller
+    /// files.
+    #[error("blender was killed while converting {0:?}, it most likely ran out of memory")]
+    OutOfMemory(PathBuf),
+    /// Blender was killed after running past [`ConversionOptions::timeout`] while converting the
+    /// blend
+    #[error("blender timed out converting {0:?}")]
+    Timeout(PathBuf),
+    /// The conversion was cancelled with [`ConversionOptions::cancellation`] or by dropping the
+    /// future of an async method such as [`ConversionOptions::convert_async`]
+    #[error("conversion cancelled")]
+    Cancelled,
+    /// An exported glTF file could not be parsed for post processing
+    #[error("invalid glTF output {0:?}")]
+    InvalidGltf(PathBuf),
+    /// The options produce more than one file so can not be converted with
+    /// [`ConversionOptions::convert_to_gltf`]
+    #[cfg(feature = "gltf")]
+    #[error("convert_to_gltf requires a single file glb or embedded gltf output")]
+    NotSingleFile,
+    /// The exported glTF could not be parsed by the `gltf` crate
+    #[cfg(feature = "gltf")]
+    #[error("gltf error: {0}")]
+    Gltf(#[from] gltf::Error),
+    /// An export failed [`PostProcess::validate`]
+    #[cfg(feature = "gltf")]
+    #[error("exported glTF {path:?} is invalid: {source}")]
+    GltfValidation {
+        /// The export
+        path: PathBuf,
+        /// What the `gltf` crate found wrong with it
+        source: gltf::Error,
+    },
+    /// gltfpack failed to optimize an export, see [`PostProcess::gltfpack`]
+    #[error("gltfpack failed to optimize {path:?}{}", stderr_suffix(.stderr))]
+    Gltfpack {
+        /// The export
+        path: PathBuf,
+        /// What gltfpack printed to stderr
+        stderr: String,
+    },
+    /// The JSON in an exported glTF file or a [`BlendManifest`] could not be parsed or written
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    /// The input directory could not be watched for changes
+    #[cfg(feature = "watch")]
+    #[error("watch error: {0}")]
+    Watch(#[from] notify::Error),
+    /// The config file could not be parsed
+    #[cfg(feature = "config")]
+    #[error("invalid config file: {0}")]
+    Config(#[from] toml::de::Error),
+    /// IOError when exporting
+    #[error("io error occurred: {0}")]
+    IOError(#[from] std::io::Error),
+}
+
+/// Writes an executable `blender` shell script running `body` into `dir` for tests to run in place
+/// of blender
+#[cfg(all(test, unix))]
+fn fake_blender(dir: &Path, body: &str) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let blender = dir.join("blender");
+    std::fs::write(&blender, format!("#!/bin/sh\n{body}")).expect("write fake blender");
+    std::fs::set_permissions(&blender, std::fs::Permissions::from_mode(0o755))
+        .expect("make fake blender executable");
+    blender
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    #[cfg(unix)]
+    #[test]
+    fn sigkill_without_output_is_oom() {
+        use std::os::unix::process::ExitStatusExt;
+        use std::process::ExitStatus;
+
+        let killed = ExitStatus::from_raw(9);
+        assert!(crate::is_oom_kill(&killed, b""));
+        assert!(!crate::is_oom_kill(&killed, b"Segmentation fault\n"));
+        assert!(!crate::is_oom_kill(&ExitStatus::from_raw(10 << 8), b""));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn crash_under_memory_limit_is_oom() {
+        use std::os::unix::process::ExitStatusExt;
+        use std::process::ExitStatus;
+
+        let failed = ExitStatus::from_raw(1 << 8);
+        assert!(crate::exceeded_memory_limit(
+            &ExitStatus::from_raw(11),
+            b"Segfault"
+        ));
+        assert!(crate::exceeded_memory_limit(
+            &failed,
+            b"Error: Malloc returns null: len=8"
+        ));
+        assert!(!crate::exceeded_memory_limit(
+            &failed,
+            b"Error: file not found"
+        ));
+        assert!(!crate::exceeded_memory_limit(
+            &ExitStatus::from_raw(0),
+            b"MemoryError"
+        ));
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn sha256_is_lowercase_hex() {
+        assert_eq!(
+            crate::sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn export_error_shows_traceback() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let mut stderr = "noise\n".repeat(30);
+        stderr.push_str("Traceback (most recent call last):\n  File \"<string>\", line 3\nRuntimeError: Error: no mesh\n");
+        let output = crate::BlenderOutput {
+            status: std::process::ExitStatus::from_raw(10 << 8),
+            stdout: Vec::new(),
+            stderr: stderr.into_bytes(),
+            timed_out: false,
+        };
+        let err = output.export_error();
+        assert!(matches!(
+            &err,
+            crate::Error::PythonException { exception, .. } if exception == "RuntimeError: Error: no mesh"
+        ));
+        assert_eq!(err.to_string(), "blender raised RuntimeError: Error: no mesh\nTraceback (most recent call last):\n  File \"<string>\", line 3\nRuntimeError: Error: no mesh");
+
+        let output = crate::BlenderOutput {
+            stderr: b"Traceback (most recent call last):\n  File \"<string>\", line 9, in export\nAttributeError: Calling operator \"bpy.ops.wm.stl_export\" error, could not be found\n".to_vec(),
+            ..output
+        };
+        assert!(
+            matches!(output.export_error(), crate::Error::UnknownOperator(op) if op == "bpy.ops.wm.stl_export")
+        );
+
+        // Without a traceback the end of stderr is kept
+        let output = crate::BlenderOutput {
+            stderr: "noise\n".repeat(30).into_bytes(),
+            ..output
+        };
+        let message = output.export_error().to_string();
+        assert!(message.starts_with("export failed with exit status: 10, stderr:\nnoise\n"));
+        assert_eq!(message.lines().count(), 21);
+    }
+
+    #[test]
+    fn baseline_profile_disables_extensions() {
+        let options = crate::ConversionOptions {
+            profile: crate::Profile::Baseline,
+            ..Default::default()
+        };
+        let script = options.export_script(Path::new("out"), Path::new("out"));
+        assert!(script.contains("export_draco_mesh_compression_enable=False"));
+        assert!(script.contains("export_lights=False"));
+        assert!(script.contains("'Emission Strength'"));
+    }
+
+    #[test]
+    fn nla_track_animations_use_track_mode() {
+        let options = crate::ConversionOptions {
+            nla_track_animations: true,
+            ..Default::default()
+        };
+        let script = options.export_script(Path::new("out"), Path::new("out"));
+        assert!(script.contains(r#"export_animation_mode="NLA_TRACKS""#));
+    }
+
+    #[test]
+    fn animation_options_filter_and_sample() {
+        let options = crate::ConversionOptions {
+            animation: crate::AnimationOptions {
+                mode: crate::AnimationMode::ActiveActions,
+                include_actions: vec!["Walk*".to_owned()],
+                frame_step: Some(2),
+                bake_constraints: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let script = options.export_script(Path::new("out"), Path::new("out"));
+        assert!(script.contains(r#"include_actions, exclude_actions = ["Walk*"], []"#));
+        assert!(script.contains("step=2, only_selected=True"));
+        assert!(script.contains(r#"export_animation_mode="ACTIVE_ACTIONS""#));
+        assert!(script.contains("export_frame_step=2"));
+        assert_eq!(options.required_version(), Some((3, 6, 0)));
+    }
+
+    #[test]
+    fn all_scenes_are_exported_with_scene_suffix() {
+        let options = crate::ConversionOptions {
+            export_scenes: crate::SceneSelection::All,
+            ..Default::default()
+        };
+        let script = options.export_script(Path::new("out/crate.glb"), Path::new("out/crate.glb"));
+        assert!(script.contains("use_active_scene=True"));
+        assert!(script.contains("    base = \"out/crate\" + '__' + scene_name\n"));
+        assert!(script.contains("    export(base + \".glb\")\n"));
+
+        // Run the name sanitizing with python if it is installed
+        let names = std::process::Command::new("python3")
+            .arg("-c")
+            .arg(format!(
+                "{}used = set()\nprint([unique_name(sanitize(n), used) for n in ['Scene', 'a/b:c?', 'scene', ' ..']])",
+                crate::SANITIZE_SCRIPT
+            ))
+            .output();
+        if let Ok(names) = names {
+            assert_eq!(
+                String::from_utf8_lossy(&names.stdout).trim(),
+                "['Scene', 'a_b_c_', 'scene_2', '_']"
+            );
+        }
+    }
+
+    #[test]
+    fn pre_export_script_runs_before_export() {
+        let options = crate::ConversionOptions {
+            pre_export_script: Some("print('PRE_EXPORT_SENTINEL')".into()),
+            post_export_script: Some(crate::PythonScript::File {
+                path: "hooks/post.py".into(),
+            }),
+            ..Default::default()
+        };
+        let script = options.export_script(Path::new("out"), Path::new("out"));
+        let pre_export = script
+            .find(r#"exec(compile("print('PRE_EXPORT_SENTINEL')", '<pre_export_script>', 'exec')"#)
+            .expect("pre export script is run");
+        assert!(pre_export < script.find("def export(").expect("export is defined"));
+        let post_export = script
+            .find(r#"exec(compile(open("hooks/post.py", encoding='utf-8').read(), "hooks/post.py", 'exec'), {'bpy': bpy, 'exported_paths': list(exported_paths)})"#)
+            .expect("post export script is run");
+        assert!(
+            post_export
+                > script
+                    .find(r#"export("out.glb")"#)
+                    .expect("export is called")
+        );
+    }
+
+    #[test]
+    fn gltf_settings_are_python_kwargs() {
+        let options = crate::ConversionOptions {
+            gltf: crate::GltfExportSettings {
+                export_cameras: true,
+                use_selection: true,
+                export_materials: crate::GltfMaterials::Placeholder,
+                draco_compression: Some(9),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let script = options.export_script(Path::new("out"), Path::new("out"));
+        assert!(script.contains("export_cameras=True, export_lights=False"));
+        assert!(script.contains(r#"export_materials="PLACEHOLDER""#));
+        assert!(script.contains("export_animations=True, export_tangents=False"));
+        assert!(script.contains(
+            "export_draco_mesh_compression_enable=True, export_draco_mesh_compression_level=6"
+        ));
+        assert!(script.contains("use_selection = True"));
+        assert!(script.contains("o in saved_selection"));
+    }
+
+    #[test]
+    fn image_quality_is_clamped_python_kwarg() {
+        let script = |image_quality| {
+            crate::ConversionOptions {
+                image_quality,
+                ..Default::default()
+            }
+            .export_script(Path::new("out"), Path::new("out"))
+        };
+        assert!(script(Some(50)).contains("export_jpeg_quality=50"));
+        assert!(script(Some(200)).contains("export_jpeg_quality=100"));
+        assert!(!script(None).contains("export_jpeg_quality"));
+    }
+
+    #[test]
+    fn scene_sidecar_is_written_next_to_export() {
+        let options = crate::ConversionOptions {
+            scene_sidecar: true,
+            exclude_cameras_and_lights: true,
+            ..Default::default()
+        };
+        let script = options.export_script(Path::new("out/crate"), Path::new("out/crate"));
+        assert!(script.contains(r#"sidecar_path = "out/crate.scene.json""#));
+        assert!(script.contains("o.type not in {'CAMERA', 'LIGHT'}"));
+    }
+
+    #[test]
+    fn manifest_is_written_next_to_export() {
+        let options = crate::ConversionOptions {
+            manifest: true,
+            ..Default::default()
+        };
+        let script = options.export_script(Path::new("out/crate"), Path::new("out/crate"));
+        assert!(script.contains(r#"manifest_path = "out/crate.meta.json""#));
+        assert!(script.contains("print('BLEND_CONVERTER_SIDECAR', manifest_path)"));
+    }
+
+    #[test]
+    fn required_property_filters_objects() {
+        let options = crate::ConversionOptions {
+            required_property: Some(("export".into(), crate::PropertyValue::Bool(true))),
+            ..Default::default()
+        };
+        let script = options.export_script(Path::new("out"), Path::new("out"));
+        assert!(script.contains(r#"return (o.get("export") == True) and o not in collision"#));
+        assert!(script.contains("use_selection = True"));
+    }
+
+    #[test]
+    fn glb_output_extension_picks_glb() {
+        let options = crate::ConversionOptions {
+            output_format: crate::OutputFormat::GltfSeparate,
+            ..Default::default()
+        };
+        let options = options.for_output(Path::new("out.glb"));
+        assert_eq!(options.output_format, crate::OutputFormat::Glb);
+        let script = options.export_script(Path::new("out.glb"), Path::new("out.glb"));
+        assert!(script.contains(r#"export_format="GLB""#));
+    }
+
+    #[test]
+    fn gltf_output_extension_picks_gltf() {
+        let default = crate::ConversionOptions::default();
+        let options = default.for_output(Path::new("out.gltf"));
+        assert_eq!(options.output_format, crate::OutputFormat::GltfEmbedded);
+        let script = options.export_script(Path::new("out.gltf"), Path::new("out.gltf"));
+        assert!(script.contains(r#"export("out.gltf")"#));
+
+        let separate = crate::ConversionOptions {
+            output_format: crate::OutputFormat::GltfSeparate,
+            ..Default::default()
+        };
+        let separate = separate.for_output(Path::new("out.gltf"));
+        assert_eq!(separate.output_format, crate::OutputFormat::GltfSeparate);
+        let unknown = default.for_output(Path::new("out.v2"));
+        assert_eq!(unknown.output_format, crate::OutputFormat::Glb);
+    }
+
+    #[test]
+    fn exported_path_matches_blender() {
+        use crate::{Exporter, OutputFormat};
+        let glb = OutputFormat::Glb;
+        assert_eq!(glb.exported_path(Path::new("a/b")), Path::new("a/b.glb"));
+        assert_eq!(glb.exported_path(Path::new("a.gltf")), Path::new("a.glb"));
+        assert_eq!(glb.exported_path(Path::new("a.v2")), Path::new("a.v2.glb"));
+        let gltf = OutputFormat::GltfSeparate;
+        assert_eq!(gltf.exported_path(Path::new("a.GLB")), Path::new("a.gltf"));
+    }
+
+    #[test]
+    fn fbx_and_obj_use_their_own_exporters() {
+        use crate::{Exporter, OutputFormat};
+        let fbx = OutputFormat::Fbx;
+        assert_eq!(fbx.exported_path(Path::new("a/b")), Path::new("a/b.fbx"));
+        assert_eq!(fbx.exported_path(Path::new("a.FBX")), Path::new("a.FBX"));
+        assert_eq!(
+            fbx.exported_path(Path::new("a.glb")),
+            Path::new("a.glb.fbx")
+        );
+        let obj = OutputFormat::Obj;
+        assert_eq!(obj.exported_path(Path::new("a/b")), Path::new("a/b.obj"));
+
+        let options = crate::ConversionOptions {
+            output_format: OutputFormat::Obj,
+            ..Default::default()
+        };
+        let script = options.export_script(Path::new("out"), Path::new("out"));
+        assert!(script.contains("bpy.ops.wm.obj_export(filepath=filepath"));
+        assert!(script.contains(r#"export("out.obj")"#));
+        assert_eq!(
+            OutputFormat::Glb.for_output(Path::new("a.fbx")),
+            OutputFormat::Fbx
+        );
+    }
+
+    #[test]
+    fn other_formats_keep_their_extensions() {
+        use crate::{Exporter, OutputFormat};
+        let usd = OutputFormat::Glb.for_output(Path::new("a.usda"));
+        assert_eq!(usd, OutputFormat::Usd);
+        assert_eq!(usd.exported_path(Path::new("a.usda")), Path::new("a.usda"));
+        assert_eq!(usd.exported_path(Path::new("a")), Path::new("a.usdc"));
+        assert_eq!(
+            OutputFormat::Alembic.exported_path(Path::new("a")),
+            Path::new("a.abc")
+        );
+
+        let options = crate::ConversionOptions {
+            output_format: OutputFormat::Stl,
+            ..Default::default()
+        };
+        let script = options.export_script(Path::new("out"), Path::new("out"));
+        assert!(script.contains("bpy.ops.wm.stl_export(filepath=filepath"));
+        assert!(script.contains(r#"export("out.stl")"#));
+    }
+
+    #[test]
+    fn collision_meshes_export_separately() {
+        let options = crate::ConversionOptions {
+            collision_pattern: Some("UCX_*".to_owned()),
+            ..Default::default()
+        };
+        let script = options.export_script(Path::new("out/crate"), Path::new("out/crate"));
+        assert!(script.contains(r#"export("out/crate.collision.glb")"#));
+        assert!(script.contains(r#"export("out/crate.glb")"#));
+        assert!(script.contains(r#"fnmatchcase(o.name, "UCX_*")"#));
+    }
+
+    #[test]
+    fn hide_render_objects_are_deselected() {
+        let options = crate::ConversionOptions {
+            respect_hide_render: true,
+            ..Default::default()
+        };
+        let script = options.export_script(Path::new("out"), Path::new("out"));
+        assert!(script.contains("return (not o.hide_render) and o not in collision"));
+        assert!(script.contains("use_selection = True"));
+    }
+
+    #[test]
+    fn object_filters_are_combined() {
+        let options = crate::ConversionOptions {
+            visible_only: true,
+            include_collections: vec!["Props".into()],
+            exclude_name_patterns: vec!["WGT-*".into(), "Ref*".into()],
+            scene: Some("Level".into()),
+            ..Default::default()
+        };
+        let script = options.export_script(Path::new("out"), Path::new("out"));
+        assert!(script.contains("return (o.visible_get() and o in included and not any("));
+        assert!(script.contains(r#"c.name in ["Props"]"#));
+        assert!(script.contains(r#"excluded_patterns = ["WGT-*", "Ref*"]"#));
+        assert!(script.contains(r#".scene = bpy.data.scenes["Level"]"#));
+        assert!(script.contains("use_selection = True"));
+    }
+
+    #[test]
+    fn flip_winding_only_flips_exported_meshes() {
+        let options = crate::ConversionOptions {
+            flip_winding: true,
+            include_collections: vec!["Props".into()],
+            ..Default::default()
+        };
+        let script = options.export_script(Path::new("out"), Path::new("out"));
+        let flip = script.find("bmesh.ops.reverse_faces").expect("flip");
+        let exportable = script.find("def exportable(o):").expect("exportable");
+        assert!(exportable < flip);
+        assert!(script.contains(
+            "for me in {o.data for o in bpy.context.view_layer.objects if o.type == 'MESH' and exportable(o)}:"
+        ));
+
+        let options = crate::ConversionOptions {
+            flip_winding: true,
+            ..Default::default()
+        };
+        let script = options.export_script(Path::new("out"), Path::new("out"));
+        assert!(script.contains(
+            "for me in {o.data for o in bpy.context.view_layer.objects if o.type == 'MESH' and True}:"
+        ));
+    }
+
+    #[test]
+    fn per_object_replaces_whole_scene_export() {
+        let options = crate::ConversionOptions {
+            per_object: true,
+            ..Default::default()
+        };
+        let script = options.export_script(Path::new("out/crate"), Path::new("out/crate"));
+        assert!(script.contains(r#"export("out/crate" + '.' + name + ext)"#));
+        assert!(!script.contains(r#"export("out/crate.glb")"#));
+    }
+
+    #[test]
+    fn thumbnail_is_rendered_after_export() {
+        let options = crate::ConversionOptions {
+            render_thumbnail: Some(crate::ThumbnailOptions {
+                width: 320,
+                engine: crate::RenderEngine::Workbench,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let script = options.export_script(Path::new("out/a"), Path::new("out/a"));
+        assert!(script.contains("width, height, samples = 320, 256, 16"));
+        assert!(script.contains("render.engine = 'BLENDER_WORKBENCH'"));
+        let export = script.find("export(\"out/a.glb\")").expect("export call");
+        let render = script
+            .find("render_thumbnail(\"out/a\" + '.thumbnail.png'")
+            .expect("render call");
+        assert!(render > export);
+    }
+
+    #[test]
+    fn per_collection_exports_top_level_collections() {
+        let options = crate::ConversionOptions {
+            per_collection: true,
+            ..Default::default()
+        };
+        let script = options.export_script(Path::new("out/props"), Path::new("out/props"));
+        assert!(script.contains("for coll in bpy.context.scene.collection.children:"));
+        assert!(script.contains(r#"export("out/props" + '.' + name + ext)"#));
+        assert!(!script.contains(r#"export("out/props.glb")"#));
+        assert!(script.contains("use_selection = True"));
+        assert!(!script.contains("def unchanged"));
+    }
+
+    #[test]
+    fn unchanged_collections_are_skipped() {
+        let options = crate::ConversionOptions {
+            per_collection: true,
+            skip_unchanged: true,
+            ..Default::default()
+        };
+        let script = options.export_script(Path::new("out/props"), Path::new("out/props"));
+        assert!(script.contains("def unchanged"));
+        let skip = script
+            .find(r#"if unchanged(objects, "out/props" + '.' + name + ext):"#)
+            .expect("skip check");
+        let select = script
+            .find("select(lambda o: o in objects)")
+            .expect("select");
+        assert!(skip < select);
+    }
+
+    #[test]
+    fn shallow_estimate_does_not_need_blender() {
+        let options = crate::ConversionOptions {
+            blender_path: Some("/nonexistent/blender".into()),
+            ..Default::default()
+        };
+        let estimates = options.estimate(Path::new("."), false).expect("estimate");
+        let (_, estimate) = estimates
+            .iter()
+            .find(|(path, _)| path.ends_with("test.blend"))
+            .expect("test.blend estimate");
+        assert!(estimate.file_size > 0);
+        assert_eq!(estimate.objects, None);
+    }
+
+    #[test]
+    fn written_files_follow_reported_files() {
+        let merged = crate::merge_written(
+            vec!["out/a.gltf".into()],
+            ["out/z.png".into(), "out/a.gltf".into(), "out/a.bin".into()],
+        );
+        let expected: Vec<std::path::PathBuf> =
+            vec!["out/a.gltf".into(), "out/a.bin".into(), "out/z.png".into()];
+        assert_eq!(merged, expected);
+    }
+
+    #[test]
+    fn extension_is_checked_on_the_given_path() {
+        // Does not exist so canonicalize would fail with an io error
+        let input = Path::new("missing|dir/asset|v2.blend.bak");
+        match crate::checked_input_path(input) {
+            Err(crate::Error::InvalidInputFile(path)) => assert_eq!(path, input),
+            result => panic!("expected invalid input file, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn move_dir_contents_preserves_layout() {
+        let root = std::env::temp_dir().join("blend_converter_move_dir_contents");
+        let _ = std::fs::remove_dir_all(&root);
+        let from = root.join("staging");
+        let to = root.join("out");
+        std::fs::create_dir_all(from.join("textures")).expect("create staging");
+        std::fs::create_dir_all(&to).expect("create out");
+        std::fs::write(from.join("a.gltf"), "{}").expect("write gltf");
+        std::fs::write(from.join("textures").join("b.png"), "").expect("write png");
+
+        crate::move_dir_contents(&from, &to).expect("move");
+        assert!(to.join("a.gltf").is_file());
+        assert!(to.join("textures").join("b.png").is_file());
+        assert!(!from.join("a.gltf").exists());
+        std::fs::remove_dir_all(&root).expect("cleanup");
+    }
+
+    #[test]
+    fn only_blend_files_are_converted() {
+        let root = std::env::temp_dir().join("blend_converter_mixed_dir");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("sub").join("dir.blend")).expect("create dirs");
+        for file in [
+            "README.txt",
+            "a.blend1",
+            "a.blend2",
+            "wood.png",
+            "sub/b.blend",
+        ] {
+            std::fs::write(root.join(file), "").expect("write file");
+        }
+
+        let found: Vec<_> = crate::blend_files(&root).collect();
+        assert_eq!(found, vec![root.join("sub").join("b.blend")]);
+        std::fs::remove_dir_all(&root).expect("cleanup");
+    }
+
+    #[test]
+    fn newer_output_is_up_to_date() {
+        let root = std::env::temp_dir().join("blend_converter_up_to_date");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).expect("create dir");
+        let input = root.join("a.blend");
+        std::fs::write(&input, "").expect("write blend");
+        let output = root.join("a");
+        let options = crate::ConversionOptions {
+            output_format: crate::OutputFormat::GltfSeparate,
+            ..Default::default()
+        };
+        assert!(!options.is_up_to_date(&input, &output));
+
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(10);
+        let gltf = std::fs::File::create(root.join("a.gltf")).expect("write gltf");
+        gltf.set_modified(later).expect("set modified");
+        assert!(!options.is_up_to_date(&input, &output), "missing .bin");
+        std::fs::write(root.join("a.bin"), "").expect("write bin");
+        assert!(options.is_up_to_date(&input, &output));
+
+        let forced = crate::ConversionOptions {
+            force: true,
+            ..options
+        };
+        assert!(!forced.is_up_to_date(&input, &output));
+        std::fs::remove_dir_all(&root).expect("cleanup");
+    }
+
+    #[test]
+    fn output_layouts_map_paths() {
+        let input = std::path::Path::new("/w/assets/blends/props/crate.blend");
+        let output_path = |layout: crate::OutputLayout| {
+            layout
+                .output_path(
+                    std::path::Path::new("/w/assets/blends"),
+                    input,
+                    std::path::Path::new("out"),
+                )
+                .expect("output path")
+        };
+        assert_eq!(
+            output_path(crate::OutputLayout::PreserveRelative),
+            std::path::Path::new("out/blends/props/crate")
+        );
+        assert_eq!(
+            output_path(crate::OutputLayout::Flatten),
+            std::path::Path::new("out/crate")
+        );
+        let custom = crate::OutputLayout::Custom(std::sync::Arc::new(|relative| {
+            std::path::Path::new("models").join(relative.with_extension(""))
+        }));
+        assert_eq!(
+            output_path(custom),
+            std::path::Path::new("out/models/props/crate")
+        );
+
+        let flatten = crate::ConversionOptions {
+            output_layout: crate::OutputLayout::Flatten,
+            ..Default::default()
+        };
+        let inputs = ["/w/a/crate.blend".into(), "/w/b/crate.blend".into()];
+        assert!(matches!(
+            flatten.check_collisions(&inputs, std::path::Path::new("/w"), std::path::Path::new("out")),
+            Err(crate::Error::OutputCollision { output, .. }) if output == std::path::Path::new("out/crate")
+        ));
+    }
+
+    #[test]
+    fn existing_output_follows_overwrite_policy() {
+        let root = std::env::temp_dir().join("blend_converter_overwrite");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).expect("create root");
+        let input = root.join("a.blend");
+        std::fs::write(&input, "").expect("write blend");
+        std::fs::write(root.join("out.glb"), "").expect("write glb");
+        // Blender is never needed as the existing output is found first
+        let export = |_: &std::path::Path, _: &std::path::Path, _: &std::path::Path| {
+            panic!("exported over an existing output")
+        };
+        let options = |overwrite| crate::ConversionOptions {
+            overwrite,
+            ..Default::default()
+        };
+        let skipped = options(crate::OverwritePolicy::Skip)
+            .convert_with(&input, &root.join("out"), export)
+            .expect("skip");
+        assert!(skipped.outputs.is_empty());
+        assert!(matches!(
+            options(crate::OverwritePolicy::Error).convert_with(&input, &root.join("out"), export),
+            Err(crate::Error::OutputExists(path)) if path == root.join("out.glb")
+        ));
+        std::fs::remove_dir_all(&root).expect("cleanup");
+    }
+
+    #[test]
+    fn plan_gives_skip_reasons() {
+        let root = std::env::temp_dir().join("blend_converter_plan");
+        let _ = std::fs::remove_dir_all(&root);
+        let input = root.join("blends");
+        let output = root.join("out");
+        std::fs::create_dir_all(&input).expect("create input");
+        std::fs::create_dir_all(&output).expect("create output");
+        for name in ["a.blend", "b.blend", "notes.txt"] {
+            std::fs::write(input.join(name), "").expect("write input");
+        }
+        let exported = |name: &str| {
+            crate::ConversionOptions::default()
+                .dir_entry_path(&input, &input.join(name), &output)
+                .expect("output path")
+                .with_extension("glb")
+        };
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(10);
+        std::fs::create_dir_all(exported("b.blend").parent().expect("parent")).expect("create dir");
+        let glb = std::fs::File::create(exported("b.blend")).expect("write glb");
+        glb.set_modified(later).expect("set modified");
+
+        let plan = crate::ConversionOptions::default()
+            .plan_dir(&input, &output)
+            .expect("plan");
+        let skip = |name: &str| {
+            plan.files
+                .iter()
+                .find(|file| file.input == input.join(name))
+                .expect("file is planned")
+                .skip
+        };
+        assert_eq!(skip("a.blend"), None);
+        assert_eq!(skip("b.blend"), Some(crate::SkipReason::UpToDate));
+        assert_eq!(skip("notes.txt"), Some(crate::SkipReason::NotBlend));
+        let to_convert: Vec<_> = plan.to_convert().collect();
+        assert_eq!(to_convert.len(), 1);
+        assert_eq!(to_convert[0].output, exported("a.blend"));
+        std::fs::remove_dir_all(&root).expect("cleanup");
+    }
+
+    /// Uses a fake blender that records each time it is run and reports every job as done
+    #[cfg(unix)]
+    #[test]
+    fn batch_runs_blender_once() {
+        let root = std::env::temp_dir().join("blend_converter_batch");
+        let _ = std::fs::remove_dir_all(&root);
+        let input = root.join("blends");
+        std::fs::create_dir_all(&input).expect("create input");
+        for i in 0..3 {
+            std::fs::write(input.join(format!("{i}.blend")), "").expect("write blend");
+        }
+        let log = root.join("log");
+        let blender = crate::fake_blender(
+            &root,
+            &format!(
+                "[ \"$2\" = -v ] && exit 0
+echo run >> {log:?}
+for i in 0 1 2; do echo \"BLEND_CONVERTER_BATCH_START $i\"; echo \"BLEND_CONVERTER_BATCH_END $i 0\"; done
+"
+            ),
+        );
+
+        let options = crate::ConversionOptions {
+            blender_path: Some(blender),
+            batch: true,
+            ..Default::default()
+        };
+        let converted = options
+            .convert_dir(&input, &root.join("out"))
+            .expect("batch conversion");
+        assert_eq!(converted.len(), 3);
+        assert_eq!(std::fs::read_to_string(&log).expect("read log"), "run\n");
+        std::fs::remove_dir_all(&root).expect("cleanup");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn progress_counts_every_blend() {
+        let root = std::env::temp_dir().join("blend_converter_progress");
+        let _ = std::fs::remove_dir_all(&root);
+        let input = root.join("blends");
+        std::fs::create_dir_all(&input).expect("create input");
+        for name in ["a", "b"] {
+            std::fs::write(input.join(format!("{name}.blend")), "").expect("write blend");
+        }
+        let blender = crate::fake_blender(&root, "exit 0\n");
+
+        let options = crate::ConversionOptions {
+            blender_path: Some(blender),
+            ..Default::default()
+        };
+        let mut events = Vec::new();
+        options
+            .convert_dir_with_progress(&input, &root.join("out"), |event| {
+                let name = |path: &std::path::Path| path.file_name().expect("file name").to_owned();
+                events.push(match event {
+                    crate::ConversionEvent::Total(total) => (format!("total {total}"), None),
+                    crate::ConversionEvent::FileStarted { path } => {
+                        ("started".to_owned(), Some(name(path)))
+                    }
+                    crate::ConversionEvent::FileFinished { path, .. } => {
+                        ("finished".to_owned(), Some(name(path)))
+                    }
+                    event => panic!("unexpected event {event:?}"),
+                });
+            })
+            .expect("conversion");
+        // Walk order is not specified so only the kinds of event are in order
+        assert_eq!(
+            events
+                .iter()
+                .map(|(kind, _)| kind.as_str())
+                .collect::<Vec<_>>(),
+            ["total 2", "started", "finished", "started", "finished"]
+        );
+        assert_eq!(events[1].1, events[2].1);
+        let mut names: Vec<_> = events.into_iter().filter_map(|(_, name)| name).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names, ["a.blend", "b.blend"]);
+        std::fs::remove_dir_all(&root).expect("cleanup");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn validate_dir_runs_jobs_at_once() {
+        let root = std::env::temp_dir().join("blend_converter_validate_jobs");
+        let _ = std::fs::remove_dir_all(&root);
+        let input = root.join("blends");
+        let started = root.join("started");
+        std::fs::create_dir_all(&input).expect("create input");
+        std::fs::create_dir_all(&started).expect("create started");
+        for name in ["good", "bad"] {
+            std::fs::write(input.join(format!("{name}.blend")), "").expect("write blend");
+        }
+        // Each validation waits for the other to start so they only pass when run together
+        let blender = crate::fake_blender(
+            &root,
+            &format!(
+                "[ \"$2\" = -v ] && exit 0
+touch {started:?}/$(basename \"$2\")
+for i in $(seq 50); do [ $(ls {started:?} | wc -l) -eq 2 ] && break; sleep 0.1; done
+[ $(ls {started:?} | wc -l) -eq 2 ] || exit 1
+case \"$2\" in *bad.blend) exit 11;; esac
+"
+            ),
+        );
+
+        let options = crate::ConversionOptions {
+            blender_path: Some(blender),
+            jobs: Some(2),
+            ..Default::default()
+        };
+        let report = options.validate_dir(&input).expect("validate dir");
+        assert_eq!(report.succeeded, [input.join("good.blend")]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, input.join("bad.blend"));
+        assert!(
+            matches!(report.failed[0].1, crate::Error::InvalidBlend(_)),
+            "{:?}",
+            report.failed[0].1
+        );
+        std::fs::remove_dir_all(&root).expect("cleanup");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn summary_collects_warnings() {
+        let root = std::env::temp_dir().join("blend_converter_summary");
+        let _ = std::fs::remove_dir_all(&root);
+        let input = root.join("blends");
+        std::fs::create_dir_all(&input).expect("create input");
+        std::fs::write(input.join("a.blend"), "").expect("write blend");
+        let blender = crate::fake_blender(
+            &root,
+            "echo 'Warning: unable to pack image'
+echo '12:00:01 | WARNING: Animation target not found' >&2
+echo 'Read blend: a.blend'
+",
+        );
+
+        let options = crate::ConversionOptions {
+            blender_path: Some(blender),
+            ..Default::default()
+        };
+        let summary = options
+            .convert_dir_summary(&input, &root.join("out"))
+            .expect("conversion");
+        assert!(summary.skipped.is_empty());
+        assert_eq!(summary.converted.len(), 1);
+        assert_eq!(
+            summary
+                .warnings()
+                .map(|(_, warning)| warning)
+                .collect::<Vec<_>>(),
+            ["unable to pack image", "Animation target not found"]
+        );
+        assert_eq!(summary.total_size(), 0);
+        std::fs::remove_dir_all(&root).expect("cleanup");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn meshes_with_ngons_are_reported() {
+        let root = std::env::temp_dir().join("blend_converter_ngons");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).expect("create root");
+        let input = root.join("a.blend");
+        std::fs::write(&input, "").expect("write blend");
+        let blender = crate::fake_blender(
+            &root,
+            "[ \"$2\" = -v ] && exit 0
+echo 'BLEND_CONVERTER_NGONS 3 Floor Plan'
+echo 'BLEND_CONVERTER_NGONS 1 Cap'
+",
+        );
+
+        let options = crate::ConversionOptions {
+            blender_path: Some(blender),
+            warn_on_ngons: true,
+            ..Default::default()
+        };
+        let blend = options
+            .convert_detailed(&input, &root.join("a.glb"))
+            .expect("conversion");
+        assert_eq!(
+            blend.ngons,
+            [("Floor Plan".to_owned(), 3), ("Cap".to_owned(), 1)]
+        );
+        std::fs::remove_dir_all(&root).expect("cleanup");
+    }
+
+    /// Uses a fake blender that only succeeds once another blender has been started alongside it
+    #[cfg(unix)]
+    #[test]
+    fn jobs_run_blender_concurrently() {
+        let root = std::env::temp_dir().join("blend_converter_jobs");
+        let _ = std::fs::remove_dir_all(&root);
+        let input = root.join("blends");
+        std::fs::create_dir_all(&input).expect("create input");
+        for name in ["a", "b"] {
+            std::fs::write(input.join(format!("{name}.blend")), "").expect("write blend");
+        }
+        let log = root.join("log");
+        let blender = crate::fake_blender(
+            &root,
+            &format!(
+                "[ \"$2\" = -v ] && exit 0
+echo start >> {log:?}
+i=0
+while [ \"$(grep -c start {log:?})\" -lt 2 ] && [ $i -lt 100 ]; do sleep 0.1; i=$((i + 1)); done
+[ \"$(grep -c start {log:?})\" -ge 2 ]
+"
+            ),
+        );
+
+        let options = crate::ConversionOptions {
+            blender_path: Some(blender),
+            jobs: Some(2),
+            ..Default::default()
+        };
+        let converted = options
+            .convert_dir(&input, &root.join("out"))
+            .expect("parallel conversion");
+        assert_eq!(converted.len(), 2);
+        std::fs::remove_dir_all(&root).expect("cleanup");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn report_collects_every_failure() {
+        let root = std::env::temp_dir().join("blend_converter_report");
+        let _ = std::fs::remove_dir_all(&root);
+        let input = root.join("blends");
+        std::fs::create_dir_all(&input).expect("create input");
+        for name in ["bad_a", "good", "bad_b"] {
+            std::fs::write(input.join(format!("{name}.blend")), "").expect("write blend");
+        }
+        let blender = crate::fake_blender(&root, "case \"$2\" in *bad*) exit 1 ;; esac\nexit 0\n");
+
+        for jobs in [None, Some(2)] {
+            let options = crate::ConversionOptions {
+                blender_path: Some(blender.clone()),
+                jobs,
+                ..Default::default()
+            };
+            let report = options
+                .convert_dir_report(&input, &root.join("out"))
+                .expect("conversion");
+            assert_eq!(report.succeeded, [input.join("good.blend")]);
+            assert_eq!(report.failed.len(), 2);
+        }
+        std::fs::remove_dir_all(&root).expect("cleanup");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hung_blender_times_out() {
+        let root = std::env::temp_dir().join("blend_converter_timeout");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).expect("create root");
+        let input = root.join("hang.blend");
+        std::fs::write(&input, "").expect("write blend");
+        let blender = crate::fake_blender(
+            &root,
+            "[ \"$2\" = -v ] && exit 0
+sleep 30
+",
+        );
+
+        let options = crate::ConversionOptions {
+            blender_path: Some(blender),
+            timeout: Some(std::time::Duration::from_millis(200)),
+            ..Default::default()
+        };
+        let start = std::time::Instant::now();
+        let err = options
+            .convert(&input, &root.join("hang.glb"))
+            .expect_err("conversion should time out");
+        assert!(matches!(err, crate::Error::Timeout(_)));
+        // The sleep inherits blender's stdout so the conversion only returns once it is killed too
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+        std::fs::remove_dir_all(&root).expect("cleanup");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hung_batch_times_out() {
+        let root = std::env::temp_dir().join("blend_converter_batch_timeout");
+        let _ = std::fs::remove_dir_all(&root);
+        let input = root.join("blends");
+        std::fs::create_dir_all(&input).expect("create input");
+        for name in ["a", "b"] {
+            std::fs::write(input.join(format!("{name}.blend")), "").expect("write blend");
+        }
+        let blender = crate::fake_blender(
+            &root,
+            "[ \"$2\" = -v ] && exit 0
+sleep 30
+",
+        );
+
+        // Being killed for the timeout must not be mistaken for hitting the memory limit
+        let options = crate::ConversionOptions {
+            blender_path: Some(blender),
+            batch: true,
+            timeout: Some(std::time::Duration::from_millis(200)),
+            memory_limit: Some(1 << 40),
+            ..Default::default()
+        };
+        let report = options
+            .convert_dir_report(&input, &root.join("out"))
+            .expect("report");
+        // A batch stops at its first failure so only the blend it was on is reported
+        assert!(report.succeeded.is_empty());
+        assert!(
+            matches!(report.failed[..], [(_, crate::Error::Timeout(_))]),
+            "{:?}",
+            report.failed
+        );
+        std::fs::remove_dir_all(&root).expect("cleanup");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cancelling_stops_directory_conversion() {
+        let root = std::env::temp_dir().join("blend_converter_cancel_token");
+        let _ = std::fs::remove_dir_all(&root);
+        let input = root.join("blends");
+        std::fs::create_dir_all(&input).expect("create input");
+        for name in ["a", "b", "c"] {
+            std::fs::write(input.join(format!("{name}.blend")), "").expect("write blend");
+        }
+        let blender = crate::fake_blender(
+            &root,
+            "[ \"$2\" = -v ] && exit 0
+echo started >> \"$(dirname \"$0\")/starts\"
+exec sleep 30
+",
+        );
+
+        let token = crate::CancellationToken::new();
+        let options = crate::ConversionOptions {
+            blender_path: Some(blender),
+            cancellation: Some(token.clone()),
+            ..Default::default()
+        };
+        let start = std::time::Instant::now();
+        let report = std::thread::scope(|scope| {
+            let conversion = scope.spawn(|| options.convert_dir_report(&input, &root.join("out")));
+            std::thread::sleep(std::time::Duration::from_millis(300));
+            token.cancel();
+            conversion.join().expect("conversion thread panicked")
+        })
+        .expect("report");
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+        assert!(matches!(report.failed[..], [(_, crate::Error::Cancelled)]));
+        let starts = std::fs::read_to_string(root.join("starts")).expect("read starts");
+        assert_eq!(starts.lines().count(), 1);
+        std::fs::remove_dir_all(&root).expect("cleanup");
+    }
+
+    #[cfg(all(unix, feature = "tokio"))]
+    #[test]
+    fn dropping_async_conversion_kills_blender() {
+        let root = std::env::temp_dir().join("blend_converter_cancel");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).expect("create root");
+        let input = root.join("hang.blend");
+        std::fs::write(&input, "").expect("write blend");
+        let blender = crate::fake_blender(
+            &root,
+            "[ \"$2\" = -v ] && exit 0
+echo $$ > \"$(dirname \"$0\")/pid\"
+exec sleep 30
+",
+        );
+
+        let options = crate::ConversionOptions {
+            blender_path: Some(blender),
+            ..Default::default()
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .expect("build runtime");
+        let output = root.join("hang.glb");
+        let result = runtime.block_on(async {
+            let timeout = std::time::Duration::from_millis(500);
+            tokio::time::timeout(timeout, options.convert_async(&input, &output)).await
+        });
+        assert!(result.is_err(), "conversion should still be running");
+
+        let pid: libc::pid_t = std::fs::read_to_string(root.join("pid"))
+            .expect("read pid")
+            .trim()
+            .parse()
+            .expect("parse pid");
+        let start = std::time::Instant::now();
+        // SAFETY: kill has no memory safety requirements
+        while unsafe { libc::kill(pid, 0) } == 0 {
+            assert!(
+                start.elapsed() < std::time::Duration::from_secs(5),
+                "blender was not killed"
+            );
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        std::fs::remove_dir_all(&root).expect("cleanup");
+    }
+
+    #[test]
+    fn formats_and_options_raise_required_version() {
+        assert_eq!(crate::ConversionOptions::default().required_version(), None);
+        let stl = crate::ConversionOptions {
+            output_format: crate::OutputFormat::Stl,
+            min_version: Some((3, 0, 0)),
+            ..Default::default()
+        };
+        assert_eq!(stl.required_version(), Some((4, 2, 0)));
+        let nla = crate::ConversionOptions {
+            nla_track_animations: true,
+            min_version: Some((4, 1, 0)),
+            ..Default::default()
+        };
+        assert_eq!(nla.required_version(), Some((4, 1, 0)));
+    }
+
+    #[test]
+    fn version_line_is_parsed() {
+        assert_eq!(
+            crate::parse_version("Blender 4.1.1\n\tbuild date: 2024-04-16\n"),
+            Some((4, 1, 1))
+        );
+        assert_eq!(
+            crate::parse_version("Gtk-Message: Failed to load module\nBlender 4.2.0 LTS\n"),
+            Some((4, 2, 0))
+        );
+        assert_eq!(
+            crate::parse_version("Blender quit\nBlender 2.79b (sub 0)\n"),
+            Some((2, 79, 0))
+        );
+        assert_eq!(crate::parse_version("Blender 3.0\n"), Some((3, 0, 0)));
+        assert_eq!(crate::parse_version("Blender quit\n"), None);
+    }
+
+    #[test]
+    fn highest_installed_version_is_found() {
+        let root = std::env::temp_dir().join("blend_converter_installs");
+        let _ = std::fs::remove_dir_all(&root);
+        let foundation = root.join("Blender Foundation");
+        let steam = root.join("steamapps").join("common").join("Blender");
+        for (dir, scripts) in [
+            (foundation.join("Blender 3.6"), "3.6"),
+            (foundation.join("Blender 4.10"), "4.10"),
+            (steam.clone(), "4.2"),
+        ] {
+            std::fs::create_dir_all(dir.join(scripts)).expect("create install");
+            std::fs::write(dir.join("blender.exe"), "").expect("write exe");
+        }
+        // Left over from an uninstall so has no executable
+        std::fs::create_dir_all(foundation.join("Blender 5.0")).expect("create install");
+
+        let mut dirs: Vec<std::path::PathBuf> = std::fs::read_dir(&foundation)
+            .expect("read installs")
+            .map(|entry| entry.expect("read entry").path())
+            .collect();
+        dirs.push(steam.clone());
+        assert_eq!(
+            crate::find_installed(&dirs),
+            Some(foundation.join("Blender 4.10").join("blender.exe"))
+        );
+        std::fs::remove_dir_all(foundation.join("Blender 4.10")).expect("remove install");
+        assert_eq!(
+            crate::find_installed(&dirs),
+            Some(steam.join("blender.exe"))
+        );
+        std::fs::remove_dir_all(&root).expect("cleanup");
+    }
+
+    #[test]
+    fn docker_runs_blender_in_image() {
+        let executable = crate::BlenderExecutable::Docker {
+            image: "blender:4.2".to_owned(),
+            mounts: vec![std::env::temp_dir()],
+        };
+        let command = executable.cmd_with_env(&[("BLENDER_USER_SCRIPTS", "/scripts".into())]);
+        assert_eq!(command.get_program(), "docker");
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args[..3], ["run", "--rm", "-i"]);
+        assert_eq!(args[args.len() - 2..], ["blender:4.2", "blender"]);
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--env", "BLENDER_USER_SCRIPTS=/scripts"]));
+        let temp = std::fs::canonicalize(std::env::temp_dir()).expect("canonical temp dir");
+        let mut volume = temp.clone().into_os_string();
+        volume.push(":");
+        volume.push(&temp);
+        assert!(args.contains(&volume.as_os_str()));
+    }
+
+    #[test]
+    fn registry_install_dirs_are_parsed() {
+        let output = "
+HKEY_LOCAL_MACHINE\\SOFTWARE\\BlenderFoundation
+    Install_Dir    REG_SZ    D:\\Tools\\Blender 4.2\\
+
+End of search: 1 match(es) found.
+";
+        assert_eq!(
+            crate::reg_string_values(output),
+            [std::path::PathBuf::from("D:\\Tools\\Blender 4.2\\")]
+        );
+    }
+
+    #[test]
+    fn export_test_blend() {
+        let options = crate::ConversionOptions::default();
+        let export_path = Path::new(".")
+            .canonicalize()
+            .expect("abs path")
+            .join("test.glb");
+        options
+            .convert(Path::new("./test.blend"), &export_path)
+            .expect("convert blend");
+        assert!(matches!(export_path.try_exists(), Ok(true)));
+    }
+
+    #[test]
+    fn export_test_blend_image_quality() {
+        // test.blend has no textures so every mesh is given a noisy one to compress
+        let texture = crate::PythonScript::Inline(
+            "import bpy, random
+random.seed(0)
+image = bpy.data.images.new('noise', 256, 256)
+image.pixels = [random.random() for _ in range(256 * 256 * 4)]
+image.pack()
+material = bpy.data.materials.new('noise')
+material.use_nodes = True
+node = material.node_tree.nodes.new('ShaderNodeTexImage')
+node.image = image
+material.node_tree.links.new(node.outputs['Color'], material.node_tree.nodes['Principled BSDF'].inputs['Base Color'])
+for o in bpy.data.objects:
+    if o.type == 'MESH':
+        o.data.materials.clear()
+        o.data.materials.append(material)
+"
+            .to_owned(),
+        );
+        let dir = Path::new(".").canonicalize().expect("abs path");
+        let size = |image_quality, name| {
+            let export_path = dir.join(name);
+            crate::ConversionOptions {
+                gltf: crate::GltfExportSettings {
+                    image_format: crate::GltfImageFormat::Jpeg,
+                    ..Default::default()
+                },
+                image_quality: Some(image_quality),
+                pre_export_script: Some(texture.clone()),
+                ..Default::default()
+            }
+            .convert(Path::new("./test.blend"), &export_path)
+            .expect("convert blend");
+            let size = std::fs::metadata(&export_path)
+                .expect("export metadata")
+                .len();
+            std::fs::remove_file(&export_path).expect("cleanup");
+            size
+        };
+        assert!(size(50, "test_q50.glb") < size(90, "test_q90.glb"));
+    }
+
+    #[test]
+    fn export_test_blend_skips_unchanged_collections() {
+        let root = std::env::temp_dir().join("blend_converter_unchanged_collections");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).expect("create root");
+        let options = crate::ConversionOptions {
+            per_collection: true,
+            skip_unchanged: true,
+            ..Default::default()
+        };
+        let input = Path::new("./test.blend").canonicalize().expect("abs path");
+        let outputs = options
+            .convert(&input, &root.join("test.glb"))
+            .expect("convert blend");
+        assert!(!outputs.is_empty());
+        let modified = |path: &Path| {
+            std::fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .expect("output modified time")
+        };
+        let first: Vec<_> = outputs.iter().map(|path| modified(path)).collect();
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        options
+            .convert(&input, &root.join("test.glb"))
+            .expect("convert blend again");
+        let second: Vec<_> = outputs.iter().map(|path| modified(path)).collect();
+        assert_eq!(first, second);
+        std::fs::remove_dir_all(&root).expect("cleanup");
     }
 }
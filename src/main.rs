@@ -0,0 +1,153 @@
+//! Command line interface for converting blend files, see `blend-converter --help`
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use blend_converter::{ConversionEvent, ConversionOptions, OutputFormat};
+use clap::{Args, Parser, Subcommand};
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Convert a single blend file
+    Convert {
+        /// The blend file to convert
+        input: PathBuf,
+        /// Where to write the export. Its extension picks the format if it matches one.
+        #[arg(short, long)]
+        output: PathBuf,
+        #[command(flatten)]
+        options: CommonArgs,
+    },
+    /// Convert every blend file in a directory, keeping the directory structure
+    ConvertDir {
+        /// The directory to search for blend files
+        input_dir: PathBuf,
+        /// The directory to write the exports to
+        #[arg(short, long)]
+        output_dir: PathBuf,
+        /// Number of blender processes to run at once
+        #[arg(short, long)]
+        jobs: Option<usize>,
+        /// Convert blends even if their outputs are up to date
+        #[arg(long)]
+        force: bool,
+        /// Keep running and convert blends again when they are saved
+        #[arg(long)]
+        watch: bool,
+        #[command(flatten)]
+        options: CommonArgs,
+    },
+}
+
+/// Options shared by every subcommand
+#[derive(Args)]
+struct CommonArgs {
+    /// The format to export to
+    #[arg(short, long, value_enum, default_value_t)]
+    format: OutputFormat,
+    /// Path to the blender executable, otherwise blender is searched for
+    #[arg(long)]
+    blender: Option<PathBuf>,
+}
+
+impl CommonArgs {
+    fn options(self) -> ConversionOptions {
+        ConversionOptions {
+            output_format: self.format,
+            blender_path: self.blender,
+            ..Default::default()
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let result = match Cli::parse().command {
+        Command::Convert {
+            input,
+            output,
+            options,
+        } => options
+            .options()
+            .convert(&input, &output)
+            .map(|outputs| print_outputs(&outputs)),
+        Command::ConvertDir {
+            input_dir,
+            output_dir,
+            jobs,
+            force,
+            watch,
+            options,
+        } => {
+            let options = ConversionOptions {
+                jobs,
+                force,
+                ..options.options()
+            };
+            convert_dir(&options, &input_dir, &output_dir, watch)
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Converts the directory logging progress to stderr and printing the written files to stdout
+fn convert_dir(
+    options: &ConversionOptions,
+    input_dir: &Path,
+    output_dir: &Path,
+    watch: bool,
+) -> Result<(), blend_converter::Error> {
+    let mut total = 0;
+    let mut done = 0;
+    let on_event = |event: ConversionEvent<'_>| match event {
+        ConversionEvent::Total(count) => {
+            total = count;
+            done = 0;
+        }
+        ConversionEvent::FileStarted { path } => {
+            eprintln!("[{}/{total}] converting {}", done + 1, path.display());
+        }
+        ConversionEvent::FileFinished {
+            path,
+            outputs,
+            duration,
+        } => {
+            done += 1;
+            eprintln!("converted {} in {duration:.1?}", path.display());
+            print_outputs(outputs);
+        }
+        ConversionEvent::FileSkipped { path } => {
+            done += 1;
+            eprintln!("{} is up to date", path.display());
+        }
+        ConversionEvent::FileFailed { path, error } => {
+            done += 1;
+            eprintln!("failed to convert {}: {error}", path.display());
+        }
+    };
+    if watch {
+        options.watch_dir(input_dir, output_dir, on_event)
+    } else {
+        options
+            .convert_dir_with_progress(input_dir, output_dir, on_event)
+            .map(|_| ())
+    }
+}
+
+fn print_outputs(outputs: &[PathBuf]) {
+    for output in outputs {
+        println!("{}", output.display());
+    }
+}
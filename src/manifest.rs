@@ -0,0 +1,116 @@
+//! Reading the manifest written by
+//! [`ConversionOptions::manifest`](crate::ConversionOptions::manifest)
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::Error;
+
+/// What a blend contains, read from the `<name>.meta.json` written by
+/// [`ConversionOptions::manifest`](crate::ConversionOptions::manifest)
+///
+/// # Example
+///
+/// ```no_run
+/// use std::path::Path;
+///
+/// let manifest = blend_converter::BlendManifest::load(Path::new("gltfs/crate.meta.json"))
+///     .expect("failed to read manifest");
+/// for animation in &manifest.animations {
+///     println!("{} {}..{}", animation.name, animation.frame_start, animation.frame_end);
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct BlendManifest {
+    /// Every object in the scene
+    pub objects: Vec<ObjectInfo>,
+    /// Every collection in the scene, parents before their children
+    pub collections: Vec<CollectionInfo>,
+    /// Every action in the blend
+    pub animations: Vec<AnimationInfo>,
+}
+
+impl BlendManifest {
+    /// Reads and parses a manifest file
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    /// The object called `name`
+    pub fn object(&self, name: &str) -> Option<&ObjectInfo> {
+        self.objects.iter().find(|object| object.name == name)
+    }
+}
+
+/// An object in a [`BlendManifest`]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct ObjectInfo {
+    /// The object's name
+    pub name: String,
+    /// Blender's name for the object's type, such as `MESH`, `ARMATURE` or `EMPTY`
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// The name of the object's parent
+    pub parent: Option<String>,
+    /// The names of the collections the object is in
+    pub collections: Vec<String>,
+    /// The names of the materials in the object's material slots
+    pub materials: Vec<String>,
+    /// The object's custom properties
+    pub properties: HashMap<String, serde_json::Value>,
+}
+
+/// A collection in a [`BlendManifest`]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct CollectionInfo {
+    /// The collection's name
+    pub name: String,
+    /// The name of the collection containing it, None for those in the scene's root collection
+    pub parent: Option<String>,
+    /// The collection's custom properties
+    pub properties: HashMap<String, serde_json::Value>,
+}
+
+/// An action in a [`BlendManifest`]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct AnimationInfo {
+    /// The action's name
+    pub name: String,
+    /// The first frame with keyframes
+    pub frame_start: f32,
+    /// The last frame with keyframes
+    pub frame_end: f32,
+    /// The names of the objects using the action
+    pub objects: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BlendManifest;
+
+    #[test]
+    fn parse_manifest() {
+        let manifest: BlendManifest = serde_json::from_str(
+            r#"{
+                "objects": [
+                    {"name": "Rig", "type": "ARMATURE", "parent": null, "collections": ["Characters"],
+                        "materials": [], "properties": {"speed": 2.5}},
+                    {"name": "Body", "type": "MESH", "parent": "Rig", "collections": ["Characters"],
+                        "materials": ["Skin"], "properties": {}}
+                ],
+                "collections": [{"name": "Characters", "parent": null, "properties": {}}],
+                "animations": [{"name": "Walk", "frame_start": 1.0, "frame_end": 24.0, "objects": ["Rig"]}]
+            }"#,
+        )
+        .expect("valid manifest");
+        let body = manifest.object("Body").expect("body object");
+        assert_eq!(body.parent.as_deref(), Some("Rig"));
+        assert_eq!(body.kind, "MESH");
+        assert_eq!(
+            manifest.object("Rig").expect("rig").properties["speed"],
+            2.5
+        );
+        assert_eq!(manifest.animations[0].objects, ["Rig"]);
+    }
+}
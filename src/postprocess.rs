@@ -0,0 +1,740 @@
+//! Post processing of exported glTF files by rewriting their JSON
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde_json::{json, Value};
+
+use crate::{Error, GltfStats, Gltfpack, RootTransform};
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_HEADER_LEN: usize = 12;
+const GLB_CHUNK_HEADER_LEN: usize = 8;
+const GLB_CHUNK_JSON: u32 = 0x4E4F_534A;
+
+/// Reads the glTF JSON from a .gltf or .glb file, applies `edit` and writes the result back.
+/// Binary chunks in a .glb are preserved unchanged.
+pub(crate) fn edit_json(
+    path: &Path,
+    edit: impl FnOnce(&mut Value) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let bytes = std::fs::read(path)?;
+    if bytes.starts_with(GLB_MAGIC) {
+        let (json, rest) =
+            split_glb(&bytes).ok_or_else(|| Error::InvalidGltf(path.to_path_buf()))?;
+        let mut json: Value = serde_json::from_slice(json)?;
+        edit(&mut json)?;
+        std::fs::write(
+            path,
+            join_glb(&bytes[4..8], &serde_json::to_vec(&json)?, rest),
+        )?;
+    } else {
+        let mut json: Value = serde_json::from_slice(&bytes)?;
+        edit(&mut json)?;
+        std::fs::write(path, serde_json::to_vec(&json)?)?;
+    }
+    Ok(())
+}
+
+/// Reads the glTF JSON from a .gltf or .glb file
+pub(crate) fn read_json(path: &Path) -> Result<Value, Error> {
+    let bytes = std::fs::read(path)?;
+    let json = if bytes.starts_with(GLB_MAGIC) {
+        split_glb(&bytes)
+            .ok_or_else(|| Error::InvalidGltf(path.to_path_buf()))?
+            .0
+    } else {
+        &bytes
+    };
+    Ok(serde_json::from_slice(json)?)
+}
+
+/// Counts what a glTF contains, `dir` is the directory its URIs are relative to
+pub(crate) fn stats(json: &Value, dir: &Path) -> GltfStats {
+    let empty = Vec::new();
+    let array = |name: &str| json.get(name).and_then(Value::as_array).unwrap_or(&empty);
+    let accessor_count = |index: Option<&Value>| {
+        index
+            .and_then(Value::as_u64)
+            .and_then(|index| array("accessors").get(index as usize))
+            .and_then(|accessor| accessor["count"].as_u64())
+            .unwrap_or(0)
+    };
+    let mut triangles = 0;
+    for primitive in array("meshes")
+        .iter()
+        .filter_map(|mesh| mesh["primitives"].as_array())
+        .flatten()
+    {
+        let vertices = match primitive.get("indices") {
+            Some(indices) => accessor_count(Some(indices)),
+            None => accessor_count(primitive["attributes"].get("POSITION")),
+        };
+        triangles += match primitive["mode"].as_u64().unwrap_or(4) {
+            4 => vertices / 3,
+            // Triangle strips and fans
+            5 | 6 => vertices.saturating_sub(2),
+            _ => 0,
+        };
+    }
+    let image_bytes = array("images")
+        .iter()
+        .map(|image| {
+            if let Some(view) = image["bufferView"].as_u64() {
+                array("bufferViews")
+                    .get(view as usize)
+                    .and_then(|view| view["byteLength"].as_u64())
+                    .unwrap_or(0)
+            } else if let Some(uri) = image["uri"].as_str() {
+                match uri.split_once(";base64,") {
+                    Some((_, data)) if uri.starts_with("data:") => data.len() as u64 / 4 * 3,
+                    _ => dir
+                        .join(decode_uri(uri))
+                        .metadata()
+                        .map_or(0, |metadata| metadata.len()),
+                }
+            } else {
+                0
+            }
+        })
+        .sum();
+    GltfStats {
+        meshes: array("meshes").len(),
+        triangles,
+        image_bytes,
+    }
+}
+
+/// Rewrites the glTF at `path` in place with gltfpack, see [`Gltfpack`]
+pub(crate) fn run_gltfpack(path: &Path, gltfpack: &Gltfpack) -> Result<(), Error> {
+    let mut cmd = Command::new("gltfpack");
+    cmd.arg("-i").arg(path).arg("-o").arg(path);
+    // Names, materials and extras are kept so the other post processing can still find them
+    cmd.args(["-kn", "-km", "-ke"]);
+    if !gltfpack.quantize {
+        cmd.arg("-noq");
+    }
+    cmd.args(&gltfpack.args);
+    let error = |stderr: String| Error::Gltfpack {
+        path: path.to_path_buf(),
+        stderr,
+    };
+    match cmd.output() {
+        Ok(result) if result.status.success() => Ok(()),
+        Ok(result) => Err(error(String::from_utf8_lossy(&result.stderr).into_owned())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Err(error(
+            "gltfpack was not found, install it from meshoptimizer".to_owned(),
+        )),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let slice = bytes.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(slice.try_into().ok()?))
+}
+
+/// Splits a glb into its JSON chunk data and the remaining chunks
+fn split_glb(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let json_len = read_u32(bytes, GLB_HEADER_LEN)? as usize;
+    if read_u32(bytes, GLB_HEADER_LEN + 4)? != GLB_CHUNK_JSON {
+        return None;
+    }
+    let json_start = GLB_HEADER_LEN + GLB_CHUNK_HEADER_LEN;
+    let json = bytes.get(json_start..json_start + json_len)?;
+    Some((json, &bytes[json_start + json_len..]))
+}
+
+/// Assembles a glb from a version, JSON chunk data and the remaining chunks
+fn join_glb(version: &[u8], json: &[u8], rest: &[u8]) -> Vec<u8> {
+    // Chunks must be 4 byte aligned and the JSON chunk is padded with spaces
+    let padding = (4 - json.len() % 4) % 4;
+    let json_len = json.len() + padding;
+    let total_len = GLB_HEADER_LEN + GLB_CHUNK_HEADER_LEN + json_len + rest.len();
+    let mut out = Vec::with_capacity(total_len);
+    out.extend_from_slice(GLB_MAGIC);
+    out.extend_from_slice(version);
+    out.extend_from_slice(&(total_len as u32).to_le_bytes());
+    out.extend_from_slice(&(json_len as u32).to_le_bytes());
+    out.extend_from_slice(&GLB_CHUNK_JSON.to_le_bytes());
+    out.extend_from_slice(json);
+    out.extend(std::iter::repeat_n(b' ', padding));
+    out.extend_from_slice(rest);
+    out
+}
+
+/// Sorts the `nodes` and `meshes` arrays by name and rewrites every reference to them so the
+/// output ordering does not depend on blender's internal ordering. Node lists in scenes and
+/// children are also sorted, skin joints keep their order because it is significant. Animation
+/// channels are remapped whether they target a node directly or through a `KHR_animation_pointer`
+/// such as `/nodes/3/rotation`.
+pub(crate) fn sort_nodes_and_meshes(json: &mut Value) {
+    let mesh_map = sort_by_name(json.get_mut("meshes"));
+    let node_map = sort_by_name(json.get_mut("nodes"));
+
+    for node in array_mut(json.get_mut("nodes")) {
+        remap_index(node.get_mut("mesh"), &mesh_map);
+        remap_index_list(node.get_mut("children"), &node_map, true);
+        if let Some(lod) = node.pointer_mut("/extensions/MSFT_lod/ids") {
+            remap_index_list(Some(lod), &node_map, false);
+        }
+    }
+    for scene in array_mut(json.get_mut("scenes")) {
+        remap_index_list(scene.get_mut("nodes"), &node_map, true);
+    }
+    for skin in array_mut(json.get_mut("skins")) {
+        remap_index(skin.get_mut("skeleton"), &node_map);
+        remap_index_list(skin.get_mut("joints"), &node_map, false);
+    }
+    for animation in array_mut(json.get_mut("animations")) {
+        for channel in array_mut(animation.get_mut("channels")) {
+            remap_index(channel.pointer_mut("/target/node"), &node_map);
+            if let Some(pointer) =
+                channel.pointer_mut("/target/extensions/KHR_animation_pointer/pointer")
+            {
+                remap_pointer(pointer, &[("nodes", &node_map), ("meshes", &mesh_map)]);
+            }
+        }
+    }
+}
+
+/// Sets `key` in the `asset.extras` object, creating it if needed. Existing extras that are not an
+/// object are replaced.
+pub(crate) fn set_asset_extra(json: &mut Value, key: &str, value: Value) {
+    let Some(root) = json.as_object_mut() else {
+        return;
+    };
+    let asset = root
+        .entry("asset")
+        .or_insert_with(|| Value::Object(Default::default()));
+    let Some(asset) = asset.as_object_mut() else {
+        return;
+    };
+    let extras = asset
+        .entry("extras")
+        .or_insert_with(|| Value::Object(Default::default()));
+    if !extras.is_object() {
+        *extras = Value::Object(Default::default());
+    }
+    if let Some(extras) = extras.as_object_mut() {
+        extras.insert(key.to_owned(), value);
+    }
+}
+
+/// Adds the `MSFT_lod` extension to nodes that have LODs. `lods` maps the name of each base node
+/// to the names of its LOD nodes, from the highest detail down. LOD nodes are removed from the
+/// scene hierarchy so they are only reachable through the extension. Bases missing from the file
+/// or missing any of their LODs, such as when exporting per object, are left alone.
+pub(crate) fn add_msft_lod(json: &mut Value, lods: &[(String, Vec<String>)]) {
+    let names: HashMap<String, usize> = array_mut(json.get_mut("nodes"))
+        .enumerate()
+        .filter_map(|(i, node)| Some((node.get("name")?.as_str()?.to_owned(), i)))
+        .collect();
+    let mut lod_nodes = Vec::new();
+    for (base, levels) in lods {
+        let Some(&base) = names.get(base) else {
+            continue;
+        };
+        let Some(ids) = levels
+            .iter()
+            .map(|level| names.get(level).copied())
+            .collect::<Option<Vec<usize>>>()
+        else {
+            continue;
+        };
+        if let Some(node) = json.pointer_mut(&format!("/nodes/{base}")) {
+            set_extension(node, "MSFT_lod", json!({ "ids": ids }));
+        }
+        lod_nodes.extend(ids);
+    }
+    if lod_nodes.is_empty() {
+        return;
+    }
+
+    let not_lod = |index: &Value| {
+        index
+            .as_u64()
+            .is_none_or(|i| !lod_nodes.contains(&(i as usize)))
+    };
+    for scene in array_mut(json.get_mut("scenes")) {
+        if let Some(nodes) = scene.get_mut("nodes").and_then(Value::as_array_mut) {
+            nodes.retain(not_lod);
+        }
+    }
+    for node in array_mut(json.get_mut("nodes")) {
+        let Some(node) = node.as_object_mut() else {
+            continue;
+        };
+        if let Some(children) = node.get_mut("children").and_then(Value::as_array_mut) {
+            children.retain(not_lod);
+            // glTF does not allow empty children arrays
+            if children.is_empty() {
+                node.remove("children");
+            }
+        }
+    }
+    add_to_extension_list(json, "extensionsUsed", "MSFT_lod");
+}
+
+/// Encodes every image file referenced by URI as KTX2 by running `toktx`, which writes the file
+/// next to the original, and points textures at it with `KHR_texture_basisu`. `dir` is the
+/// directory URIs are relative to. The originals are removed once everything is encoded.
+pub(crate) fn encode_ktx2(json: &mut Value, dir: &Path, toktx: &Path) -> Result<(), Error> {
+    let mut encoded = HashSet::new();
+    let mut originals = Vec::new();
+    for (index, image) in array_mut(json.get_mut("images")).enumerate() {
+        let Some(uri) = image.get("uri").and_then(Value::as_str) else {
+            continue;
+        };
+        let Some((stem, _)) = uri.rsplit_once('.').filter(|_| !uri.starts_with("data:")) else {
+            continue;
+        };
+        let new_uri = format!("{stem}.ktx2");
+        let original = dir.join(decode_uri(uri));
+        let output = dir.join(decode_uri(&new_uri));
+        run_toktx(toktx, &original, &output)?;
+        image["uri"] = json!(new_uri);
+        image["mimeType"] = json!("image/ktx2");
+        encoded.insert(index as u64);
+        originals.push(original);
+    }
+    if encoded.is_empty() {
+        return Ok(());
+    }
+    for texture in array_mut(json.get_mut("textures")) {
+        let Some(object) = texture.as_object_mut() else {
+            continue;
+        };
+        let Some(source) = object.get("source").and_then(Value::as_u64) else {
+            continue;
+        };
+        if encoded.contains(&source) {
+            object.remove("source");
+            set_extension(texture, "KHR_texture_basisu", json!({ "source": source }));
+        }
+    }
+    // Without a fallback source viewers must support the extension to show the textures
+    add_to_extension_list(json, "extensionsUsed", "KHR_texture_basisu");
+    add_to_extension_list(json, "extensionsRequired", "KHR_texture_basisu");
+    for original in originals {
+        // Several images can share a file
+        if original.is_file() {
+            std::fs::remove_file(original)?;
+        }
+    }
+    Ok(())
+}
+
+fn run_toktx(toktx: &Path, input: &Path, output: &Path) -> Result<(), Error> {
+    let encode_error = |stderr: String| Error::TextureEncoding {
+        image: input.to_path_buf(),
+        stderr,
+    };
+    let result = Command::new(toktx)
+        .args(["--t2", "--encode", "uastc", "--genmipmap"])
+        .arg(output)
+        .arg(input)
+        .output();
+    match result {
+        Ok(result) if result.status.success() => Ok(()),
+        Ok(result) => Err(encode_error(
+            String::from_utf8_lossy(&result.stderr).into_owned(),
+        )),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Err(encode_error(format!(
+            "{} was not found, install KTX-Software",
+            toktx.display()
+        ))),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Decodes the percent encoded characters of a relative URI into a path
+fn decode_uri(uri: &str) -> PathBuf {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+/// Adds `name` to the root array `list`, either `extensionsUsed` or `extensionsRequired`
+fn add_to_extension_list(json: &mut Value, list: &str, name: &str) {
+    if let Some(root) = json.as_object_mut() {
+        let used = root.entry(list).or_insert_with(|| json!([]));
+        if let Some(used) = used.as_array_mut() {
+            if !used.iter().any(|ext| ext == name) {
+                used.push(json!(name));
+            }
+        }
+    }
+}
+
+/// Moves the root nodes of each scene under new nodes with `transform`. A node can only have one
+/// parent, so roots are grouped by the scenes they are in: roots in exactly the same scenes share
+/// a wrapper, and a scene whose roots partly overlap another's gets one wrapper per group.
+pub(crate) fn wrap_roots(json: &mut Value, transform: &RootTransform) {
+    let Some(root) = json.as_object_mut() else {
+        return;
+    };
+    let node_count = root
+        .get("nodes")
+        .and_then(Value::as_array)
+        .map_or(0, Vec::len);
+    let scene_roots: Vec<Vec<Value>> = array_mut(root.get_mut("scenes"))
+        .map(|scene| {
+            scene
+                .get("nodes")
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default()
+        })
+        .collect();
+    // The scenes each group of roots is in, and the roots
+    let mut groups: Vec<(Vec<usize>, Vec<Value>)> = Vec::new();
+    for (index, roots) in scene_roots.iter().enumerate() {
+        for node in roots {
+            let scenes: Vec<usize> = (0..scene_roots.len())
+                .filter(|&scene| scene_roots[scene].contains(node))
+                .collect();
+            // Grouped when its first scene was reached
+            if scenes[0] != index {
+                continue;
+            }
+            match groups.iter_mut().find(|(existing, _)| *existing == scenes) {
+                Some((_, group)) if group.contains(node) => {}
+                Some((_, group)) => group.push(node.clone()),
+                None => groups.push((scenes, vec![node.clone()])),
+            }
+        }
+    }
+    for (index, scene) in array_mut(root.get_mut("scenes")).enumerate() {
+        let wrappers: Vec<usize> = groups
+            .iter()
+            .enumerate()
+            .filter(|(_, (scenes, _))| scenes.contains(&index))
+            .map(|(group, _)| node_count + group)
+            .collect();
+        if let Some(nodes) = scene.get_mut("nodes").filter(|_| !wrappers.is_empty()) {
+            *nodes = json!(wrappers);
+        }
+    }
+    if !groups.is_empty() {
+        let nodes = root.entry("nodes").or_insert_with(|| json!([]));
+        if let Some(nodes) = nodes.as_array_mut() {
+            nodes.extend(groups.into_iter().map(|(_, roots)| {
+                json!({
+                    "children": roots,
+                    "rotation": transform.rotation,
+                    "scale": transform.scale,
+                })
+            }));
+        }
+    }
+}
+
+fn set_extension(value: &mut Value, name: &str, extension: Value) {
+    let Some(object) = value.as_object_mut() else {
+        return;
+    };
+    let extensions = object.entry("extensions").or_insert_with(|| json!({}));
+    if let Some(extensions) = extensions.as_object_mut() {
+        extensions.insert(name.to_owned(), extension);
+    }
+}
+
+pub(crate) fn array_mut(value: Option<&mut Value>) -> impl Iterator<Item = &mut Value> {
+    value
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flat_map(|array| array.iter_mut())
+}
+
+/// Stable sorts an array of objects by their `name` and returns a map from old to new index
+fn sort_by_name(value: Option<&mut Value>) -> Vec<usize> {
+    let Some(array) = value.and_then(Value::as_array_mut) else {
+        return Vec::new();
+    };
+    let mut order: Vec<usize> = (0..array.len()).collect();
+    let name = |i: &usize| array[*i].get("name").and_then(Value::as_str).unwrap_or("");
+    order.sort_by(|a, b| name(a).cmp(name(b)));
+
+    let mut old_to_new = vec![0; array.len()];
+    for (new, old) in order.iter().enumerate() {
+        old_to_new[*old] = new;
+    }
+    let mut items: Vec<Option<Value>> = array.drain(..).map(Some).collect();
+    array.extend(
+        order
+            .iter()
+            .map(|old| items[*old].take().expect("each index once")),
+    );
+    old_to_new
+}
+
+fn remap_index(value: Option<&mut Value>, map: &[usize]) {
+    if let Some(value) = value {
+        if let Some(new) = value.as_u64().and_then(|old| map.get(old as usize)) {
+            *value = Value::from(*new);
+        }
+    }
+}
+
+/// Remaps the index in a JSON pointer string into one of the `arrays`, such as the 3 in
+/// `/nodes/3/rotation`. Pointers into other arrays are left alone.
+fn remap_pointer(value: &mut Value, arrays: &[(&str, &[usize])]) {
+    let Some(pointer) = value.as_str() else {
+        return;
+    };
+    let mut parts = pointer.splitn(4, '/');
+    let (Some(""), Some(array), Some(index)) = (parts.next(), parts.next(), parts.next()) else {
+        return;
+    };
+    let Some((_, map)) = arrays.iter().find(|(name, _)| *name == array) else {
+        return;
+    };
+    let Some(new) = index.parse::<usize>().ok().and_then(|old| map.get(old)) else {
+        return;
+    };
+    let rest = parts
+        .next()
+        .map(|rest| format!("/{rest}"))
+        .unwrap_or_default();
+    *value = Value::from(format!("/{array}/{new}{rest}"));
+}
+
+fn remap_index_list(value: Option<&mut Value>, map: &[usize], sort: bool) {
+    let Some(list) = value.and_then(Value::as_array_mut) else {
+        return;
+    };
+    for index in list.iter_mut() {
+        remap_index(Some(index), map);
+    }
+    if sort {
+        list.sort_by_key(|index| index.as_u64());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    #[test]
+    fn sorting_remaps_references() {
+        let mut gltf = json!({
+            "scenes": [{ "nodes": [0, 2] }],
+            "nodes": [
+                { "name": "c", "mesh": 0, "children": [1] },
+                { "name": "a" },
+                { "name": "b", "mesh": 1 },
+            ],
+            "meshes": [{ "name": "z" }, { "name": "y" }],
+            "animations": [{ "channels": [
+                { "target": { "node": 1 } },
+                { "target": { "extensions": { "KHR_animation_pointer": {
+                    "pointer": "/nodes/2/extensions/KHR_node_visibility/visible"
+                } } } },
+                { "target": { "extensions": { "KHR_animation_pointer": {
+                    "pointer": "/meshes/0/weights"
+                } } } },
+                { "target": { "extensions": { "KHR_animation_pointer": {
+                    "pointer": "/materials/0/alphaCutoff"
+                } } } },
+            ] }],
+        });
+        super::sort_nodes_and_meshes(&mut gltf);
+        assert_eq!(
+            gltf,
+            json!({
+                "scenes": [{ "nodes": [1, 2] }],
+                "nodes": [
+                    { "name": "a" },
+                    { "name": "b", "mesh": 0 },
+                    { "name": "c", "mesh": 1, "children": [0] },
+                ],
+                "meshes": [{ "name": "y" }, { "name": "z" }],
+                "animations": [{ "channels": [
+                    { "target": { "node": 0 } },
+                    { "target": { "extensions": { "KHR_animation_pointer": {
+                        "pointer": "/nodes/1/extensions/KHR_node_visibility/visible"
+                    } } } },
+                    { "target": { "extensions": { "KHR_animation_pointer": {
+                        "pointer": "/meshes/1/weights"
+                    } } } },
+                    { "target": { "extensions": { "KHR_animation_pointer": {
+                        "pointer": "/materials/0/alphaCutoff"
+                    } } } },
+                ] }],
+            })
+        );
+    }
+
+    #[test]
+    fn asset_extras_are_merged() {
+        let mut gltf = json!({ "asset": { "version": "2.0", "extras": { "a": 1 } } });
+        super::set_asset_extra(&mut gltf, "source_blend", json!("a.blend"));
+        assert_eq!(
+            gltf,
+            json!({ "asset": { "version": "2.0", "extras": { "a": 1, "source_blend": "a.blend" } } })
+        );
+    }
+
+    #[test]
+    fn lods_are_moved_into_extension() {
+        let mut gltf = json!({
+            "scenes": [{ "nodes": [0, 2] }],
+            "nodes": [
+                { "name": "rock", "children": [1] },
+                { "name": "rock.LOD2" },
+                { "name": "rock.LOD1" },
+            ],
+        });
+        let lods = [(
+            "rock".to_owned(),
+            vec!["rock.LOD1".to_owned(), "rock.LOD2".to_owned()],
+        )];
+        super::add_msft_lod(&mut gltf, &lods);
+        assert_eq!(
+            gltf,
+            json!({
+                "scenes": [{ "nodes": [0] }],
+                "nodes": [
+                    { "name": "rock", "extensions": { "MSFT_lod": { "ids": [2, 1] } } },
+                    { "name": "rock.LOD2" },
+                    { "name": "rock.LOD1" },
+                ],
+                "extensionsUsed": ["MSFT_lod"],
+            })
+        );
+    }
+
+    #[test]
+    fn every_scene_root_is_wrapped() {
+        let mut gltf = json!({
+            "scenes": [{ "nodes": [0, 1] }, { "nodes": [2] }, { "nodes": [0, 1] }],
+            "nodes": [{ "name": "a" }, { "name": "b" }, { "name": "c" }],
+        });
+        super::wrap_roots(&mut gltf, &crate::RootTransform::uniform_scale(0.01));
+        let wrapper = |children| json!({ "children": children, "rotation": [0.0, 0.0, 0.0, 1.0], "scale": [0.01, 0.01, 0.01] });
+        assert_eq!(
+            gltf,
+            json!({
+                "scenes": [{ "nodes": [3] }, { "nodes": [4] }, { "nodes": [3] }],
+                "nodes": [
+                    { "name": "a" },
+                    { "name": "b" },
+                    { "name": "c" },
+                    wrapper(json!([0, 1])),
+                    wrapper(json!([2])),
+                ],
+            })
+        );
+
+        // Node 1 is in both scenes so gets its own wrapper shared by them
+        let mut gltf = json!({
+            "scenes": [{ "nodes": [0, 1] }, { "nodes": [1, 2] }],
+            "nodes": [{ "name": "a" }, { "name": "b" }, { "name": "c" }],
+        });
+        super::wrap_roots(&mut gltf, &crate::RootTransform::uniform_scale(0.01));
+        assert_eq!(
+            gltf,
+            json!({
+                "scenes": [{ "nodes": [3, 4] }, { "nodes": [4, 5] }],
+                "nodes": [
+                    { "name": "a" },
+                    { "name": "b" },
+                    { "name": "c" },
+                    wrapper(json!([0])),
+                    wrapper(json!([1])),
+                    wrapper(json!([2])),
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn stats_count_triangles_and_images() {
+        let gltf = json!({
+            "meshes": [
+                { "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1 }] },
+                { "primitives": [
+                    { "attributes": { "POSITION": 0 } },
+                    { "attributes": { "POSITION": 0 }, "mode": 5 },
+                    { "attributes": { "POSITION": 0 }, "mode": 1 },
+                ] },
+            ],
+            "accessors": [{ "count": 6 }, { "count": 36 }],
+            "bufferViews": [{ "byteLength": 1000 }],
+            "images": [{ "bufferView": 0 }, { "uri": "data:image/png;base64,AAAAAAAA" }],
+        });
+        assert_eq!(
+            super::stats(&gltf, std::path::Path::new("")),
+            crate::GltfStats {
+                meshes: 2,
+                triangles: 12 + 2 + 4,
+                image_bytes: 1006,
+            }
+        );
+    }
+
+    #[test]
+    fn glb_round_trip() {
+        let bin = [1, 0, 0, 0, b'B', b'I', b'N', 0, 7, 0, 0, 0];
+        let glb = super::join_glb(&2u32.to_le_bytes(), br#"{"a":1}"#, &bin);
+        assert_eq!(glb.len() % 4, 0);
+        let (json, rest) = super::split_glb(&glb).expect("valid glb");
+        assert_eq!(json, br#"{"a":1} "#);
+        assert_eq!(rest, bin);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ktx2_replaces_image_sources() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = std::env::temp_dir().join("blend_converter_ktx2");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("tex")).expect("create root");
+        let toktx = root.join("toktx");
+        std::fs::write(&toktx, "#!/bin/sh\ncp \"$6\" \"$5\"\n").expect("write fake toktx");
+        std::fs::set_permissions(&toktx, std::fs::Permissions::from_mode(0o755))
+            .expect("make fake toktx executable");
+        std::fs::write(root.join("tex/a b.png"), "png").expect("write image");
+
+        let mut gltf = json!({
+            "images": [{ "uri": "tex/a%20b.png", "mimeType": "image/png" }],
+            "textures": [{ "source": 0, "sampler": 0 }],
+        });
+        super::encode_ktx2(&mut gltf, &root, &toktx).expect("encode");
+        assert_eq!(
+            gltf,
+            json!({
+                "images": [{ "uri": "tex/a%20b.ktx2", "mimeType": "image/ktx2" }],
+                "textures": [{
+                    "sampler": 0,
+                    "extensions": { "KHR_texture_basisu": { "source": 0 } },
+                }],
+                "extensionsUsed": ["KHR_texture_basisu"],
+                "extensionsRequired": ["KHR_texture_basisu"],
+            })
+        );
+        assert!(root.join("tex/a b.ktx2").is_file());
+        assert!(!root.join("tex/a b.png").exists());
+        std::fs::remove_dir_all(&root).expect("cleanup");
+    }
+}
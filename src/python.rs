@@ -0,0 +1,87 @@
+//! Helpers for generating the python passed to blender
+
+use std::path::Path;
+
+/// Keyword arguments passed to a python function call. Setting the same keyword twice replaces the
+/// earlier value so later options can override earlier ones.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct PyKwargs(Vec<(&'static str, String)>);
+
+impl PyKwargs {
+    pub(crate) fn set(&mut self, key: &'static str, value: impl Into<String>) {
+        let value = value.into();
+        if let Some(entry) = self.0.iter_mut().find(|(k, _)| *k == key) {
+            entry.1 = value;
+        } else {
+            self.0.push((key, value));
+        }
+    }
+}
+
+impl std::fmt::Display for PyKwargs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (key, value)) in self.0.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{key}={value}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats a bool as a python bool literal
+pub(crate) fn format_py_bool(val: bool) -> &'static str {
+    if val {
+        "True"
+    } else {
+        "False"
+    }
+}
+
+/// Formats a string as a python string literal
+pub(crate) fn format_py_str(val: &str) -> String {
+    let mut out = String::with_capacity(val.len() + 2);
+    out.push('"');
+    for c in val.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Formats strings as a python list of string literals
+pub(crate) fn format_py_str_list(vals: &[String]) -> String {
+    let items: Vec<String> = vals.iter().map(|val| format_py_str(val)).collect();
+    format!("[{}]", items.join(", "))
+}
+
+/// Formats a path as a python string literal
+pub(crate) fn format_py_path(path: &Path) -> String {
+    format_py_str(&path.to_string_lossy())
+}
+
+/// Indents every line of `code` by `levels` levels of four spaces
+pub(crate) fn indent(code: &str, levels: usize) -> String {
+    let prefix = "    ".repeat(levels);
+    code.lines()
+        .map(|line| format!("{prefix}{line}\n"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn string_escaping() {
+        assert_eq!(super::format_py_str("a\"b\\c\nd"), r#""a\"b\\c\nd""#);
+        assert_eq!(super::format_py_str("\u{7}é"), r#""\u0007é""#);
+    }
+}
@@ -0,0 +1,425 @@
+//! Blender processes kept running between conversions so small blends do not each pay blender's
+//! startup time
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+
+use crate::{
+    exit_status, BlenderExecutable, BlenderOutput, ConversionOptions, Error, BATCH_END_MARKER,
+    BATCH_START_MARKER,
+};
+
+/// Line printed to stderr by a session's blender once everything a job wrote to stderr has been
+/// written. Stderr is read on its own thread so this is how a job knows it has all of it.
+const STDERR_END_MARKER: &str = "BLEND_CONVERTER_STDERR_END";
+
+/// Python run by a session's blender. Each line of stdin is a job, a JSON object with the blend
+/// to open and the export script to run in it, and each job's output is marked like a job of a
+/// [`ConversionOptions::batch`].
+fn session_script() -> String {
+    format!(
+        "import bpy, json, sys, traceback
+while True:
+    line = sys.stdin.readline()
+    if not line:
+        break
+    job = json.loads(line)
+    print('{BATCH_START_MARKER}', 0, flush=True)
+    try:
+        bpy.ops.wm.open_mainfile(filepath=job['input'], load_ui=False)
+        exec(job['script'], {{}})
+        code = 0
+    except SystemExit as e:
+        code = e.code if isinstance(e.code, int) else int(e.code is not None)
+    except Exception:
+        traceback.print_exc()
+        code = 10
+    print('{STDERR_END_MARKER}', file=sys.stderr, flush=True)
+    print('{BATCH_END_MARKER}', 0, code, flush=True)
+"
+    )
+}
+
+/// A blender process that keeps running to convert one blend after another, avoiding the few
+/// seconds blender takes to start for every blend. Blends are converted like
+/// [`ConversionOptions::convert`] with the options the session was started with.
+///
+/// Every conversion runs in the same blender so state a blend leaves behind outside its own
+/// data, such as registered add-ons, can affect the next. If blender exits, for example by
+/// crashing, the conversion fails and the next one starts a new blender.
+/// [`ConversionOptions::timeout`] is not applied and [`ConversionOptions::cancellation`] is only
+/// checked before each conversion starts, a running conversion is not interrupted.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::path::Path;
+///
+/// let mut session = blend_converter::BlenderSession::new(&Default::default())
+///     .expect("failed to start blender");
+/// for name in ["crate", "barrel", "lamp"] {
+///     let input = Path::new("blends").join(name).with_extension("blend");
+///     session
+///         .convert(&input, &Path::new("gltfs").join(name))
+///         .expect("failed to convert blend");
+/// }
+/// ```
+#[derive(Debug)]
+pub struct BlenderSession {
+    options: ConversionOptions,
+    blender_exe: BlenderExecutable,
+    process: Option<SessionProcess>,
+}
+
+/// The running blender of a [`BlenderSession`]
+#[derive(Debug)]
+struct SessionProcess {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    stderr: Arc<(Mutex<SessionStderr>, Condvar)>,
+    stderr_thread: JoinHandle<()>,
+}
+
+/// The stderr of a session's blender, shared with the thread reading it
+#[derive(Debug, Default)]
+struct SessionStderr {
+    /// Stderr written since the last job finished
+    text: Vec<u8>,
+    /// The reader has seen the end of the current job's stderr
+    job_ended: bool,
+    /// Blender has closed stderr so no more will be written
+    closed: bool,
+}
+
+impl SessionProcess {
+    /// Waits for the rest of the current job's stderr and takes it
+    fn job_stderr(&self) -> Vec<u8> {
+        let (stderr, changed) = &*self.stderr;
+        let mut stderr = changed
+            .wait_while(stderr.lock().expect("stderr lock poisoned"), |stderr| {
+                !stderr.job_ended && !stderr.closed
+            })
+            .expect("stderr lock poisoned");
+        stderr.job_ended = false;
+        std::mem::take(&mut stderr.text)
+    }
+}
+
+impl BlenderSession {
+    /// Finds blender like [`ConversionOptions::convert`] and starts it
+    pub fn new(options: &ConversionOptions) -> Result<Self, Error> {
+        Self::with_executable(options, BlenderExecutable::find_using_options(options)?)
+    }
+
+    fn with_executable(
+        options: &ConversionOptions,
+        blender_exe: BlenderExecutable,
+    ) -> Result<Self, Error> {
+        let mut session = Self {
+            options: options.clone(),
+            blender_exe,
+            process: None,
+        };
+        session.process = Some(session.start()?);
+        Ok(session)
+    }
+
+    /// Convert an individual blend file, see [`ConversionOptions::convert`]
+    pub fn convert(&mut self, input: &Path, output: &Path) -> Result<Vec<PathBuf>, Error> {
+        if self
+            .options
+            .cancellation
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled())
+        {
+            return Err(Error::Cancelled);
+        }
+        let options = self.options.for_output(output).into_owned();
+        let exported =
+            options.convert_with(input, output, |input_file_path, output, existing_output| {
+                let script = options.export_script(output, existing_output);
+                let job_output = self.run_job(&options, input_file_path, &script)?;
+                options.finish_export(input_file_path, &job_output)
+            })?;
+        Ok(exported.outputs)
+    }
+
+    fn start(&self) -> Result<SessionProcess, Error> {
+        let mut cmd = self.blender_exe.cmd_with_env(&self.options.blender_env()?);
+        #[cfg(target_os = "linux")]
+        if let Some(limit) = self.options.memory_limit {
+            crate::limit_address_space(&mut cmd, limit);
+        }
+        cmd.arg("-b")
+            .arg("--python-exit-code")
+            .arg("10")
+            .arg("--python-expr")
+            .arg(session_script())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let mut child = cmd.spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        let mut child_stderr = BufReader::new(child.stderr.take().expect("stderr is piped"));
+        let stderr = Arc::new((Mutex::new(SessionStderr::default()), Condvar::new()));
+        let collected = Arc::clone(&stderr);
+        let stderr_thread = std::thread::spawn(move || {
+            let (collected, changed) = &*collected;
+            let mut line = Vec::new();
+            while child_stderr
+                .read_until(b'\n', &mut line)
+                .is_ok_and(|read| read > 0)
+            {
+                let mut collected = collected.lock().expect("stderr lock poisoned");
+                if line.starts_with(STDERR_END_MARKER.as_bytes()) {
+                    collected.job_ended = true;
+                    changed.notify_all();
+                    line.clear();
+                } else {
+                    let _ = std::io::stderr().write_all(&line);
+                    collected.text.append(&mut line);
+                }
+            }
+            collected.lock().expect("stderr lock poisoned").closed = true;
+            changed.notify_all();
+        });
+        Ok(SessionProcess {
+            child,
+            stdin,
+            stdout,
+            stderr,
+            stderr_thread,
+        })
+    }
+
+    /// Sends a job to blender, starting blender first if it is not running, and waits for it to
+    /// finish. Blender's stdout and stderr are passed through as they are written.
+    fn run_job(
+        &mut self,
+        options: &ConversionOptions,
+        input_file_path: &Path,
+        script: &str,
+    ) -> Result<BlenderOutput, Error> {
+        if self.process.is_none() {
+            self.process = Some(self.start()?);
+        }
+        let process = self.process.as_mut().expect("blender was started");
+        let job = serde_json::json!({
+            "input": input_file_path.to_string_lossy(),
+            "script": script,
+        });
+        // If blender has exited reading its output below reports why
+        if let Err(err) = writeln!(process.stdin, "{job}") {
+            if err.kind() != std::io::ErrorKind::BrokenPipe {
+                return Err(err.into());
+            }
+        }
+
+        let mut stdout = Vec::new();
+        let mut line = Vec::new();
+        let code = loop {
+            line.clear();
+            if process.stdout.read_until(b'\n', &mut line)? == 0 {
+                break None;
+            }
+            std::io::stdout().write_all(&line)?;
+            let text = String::from_utf8_lossy(&line);
+            if text.starts_with(BATCH_START_MARKER) {
+                // Discard what blender printed while starting or loading
+                stdout.clear();
+            } else if let Some(rest) = text.strip_prefix(BATCH_END_MARKER) {
+                break Some(
+                    rest.split_whitespace()
+                        .nth(1)
+                        .and_then(|code| code.parse().ok())
+                        .unwrap_or(1),
+                );
+            } else {
+                stdout.extend_from_slice(&line);
+            }
+        };
+        if let Some(code) = code {
+            let stderr = process.job_stderr();
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                input = %input_file_path.display(),
+                code,
+                stdout = %String::from_utf8_lossy(&stdout),
+                stderr = %String::from_utf8_lossy(&stderr),
+                "blender session job finished"
+            );
+            return Ok(BlenderOutput {
+                status: exit_status(code),
+                stdout,
+                stderr,
+                timed_out: false,
+            });
+        }
+
+        // Blender exited before finishing the job
+        let mut process = self.process.take().expect("blender was started");
+        let status = process.child.wait()?;
+        let stderr = process.job_stderr();
+        let _ = process.stderr_thread.join();
+        let output = BlenderOutput {
+            status,
+            stdout,
+            stderr,
+            timed_out: false,
+        };
+        if options.is_out_of_memory(&output) {
+            Err(Error::OutOfMemory(input_file_path.to_path_buf()))
+        } else {
+            Err(output.export_error())
+        }
+    }
+}
+
+impl Drop for BlenderSession {
+    fn drop(&mut self) {
+        if let Some(process) = self.process.take() {
+            // Closing stdin ends the session script so blender exits
+            drop(process.stdin);
+            let mut child = process.child;
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Several [`BlenderSession`]s shared between threads, so blends can be converted concurrently
+/// without starting blender for each one. Exports are staged like [`ConversionOptions::atomic`]
+/// so concurrent conversions into one directory report only their own files.
+#[derive(Debug)]
+pub struct BlenderPool {
+    sessions: Mutex<Vec<BlenderSession>>,
+    returned: Condvar,
+}
+
+impl BlenderPool {
+    /// Starts `size` blender processes, at least one
+    pub fn new(options: &ConversionOptions, size: usize) -> Result<Self, Error> {
+        let options = ConversionOptions {
+            atomic: true,
+            ..options.clone()
+        };
+        let blender_exe = BlenderExecutable::find_using_options(&options)?;
+        let sessions = (0..size.max(1))
+            .map(|_| BlenderSession::with_executable(&options, blender_exe.clone()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            sessions: Mutex::new(sessions),
+            returned: Condvar::new(),
+        })
+    }
+
+    /// Convert an individual blend file with the next free session, waiting for one if they are
+    /// all busy. See [`ConversionOptions::convert`].
+    pub fn convert(&self, input: &Path, output: &Path) -> Result<Vec<PathBuf>, Error> {
+        let mut session = {
+            let mut sessions = self.sessions.lock().expect("pool lock poisoned");
+            loop {
+                if let Some(session) = sessions.pop() {
+                    break session;
+                }
+                sessions = self.returned.wait(sessions).expect("pool lock poisoned");
+            }
+        };
+        let result = session.convert(input, output);
+        self.sessions
+            .lock()
+            .expect("pool lock poisoned")
+            .push(session);
+        self.returned.notify_one();
+        result
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::BlenderSession;
+
+    #[test]
+    fn session_runs_blender_once() {
+        let root = std::env::temp_dir().join("blend_converter_session");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).expect("create root");
+        let blender = crate::fake_blender(
+            &root,
+            "[ \"$2\" = -v ] && exit 0
+echo started >> \"$(dirname \"$0\")/starts\"
+while read line; do
+    echo BLEND_CONVERTER_BATCH_START 0
+    echo BLEND_CONVERTER_STDERR_END >&2
+    echo BLEND_CONVERTER_BATCH_END 0 0
+done
+",
+        );
+
+        let options = crate::ConversionOptions {
+            blender_path: Some(blender),
+            ..Default::default()
+        };
+        let mut session = BlenderSession::new(&options).expect("start session");
+        for name in ["a", "b", "c"] {
+            let input = root.join(name).with_extension("blend");
+            std::fs::write(&input, "").expect("write blend");
+            session
+                .convert(&input, &root.join(name))
+                .expect("conversion");
+        }
+        drop(session);
+        let starts = std::fs::read_to_string(root.join("starts")).expect("read starts");
+        assert_eq!(starts.lines().count(), 1);
+        std::fs::remove_dir_all(&root).expect("cleanup");
+    }
+    /// Uses a fake blender that finishes the job on stdout before writing its traceback to stderr
+    #[test]
+    fn late_stderr_belongs_to_its_job() {
+        let root = std::env::temp_dir().join("blend_converter_session_stderr");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).expect("create root");
+        let blender = crate::fake_blender(
+            &root,
+            "[ \"$2\" = -v ] && exit 0
+while read line; do
+    echo BLEND_CONVERTER_BATCH_START 0
+    echo BLEND_CONVERTER_BATCH_END 0 10
+    sleep 0.3
+    printf 'Traceback (most recent call last):\\nRuntimeError: boom\\n' >&2
+    echo BLEND_CONVERTER_STDERR_END >&2
+done
+",
+        );
+
+        let token = crate::CancellationToken::new();
+        let options = crate::ConversionOptions {
+            blender_path: Some(blender),
+            cancellation: Some(token.clone()),
+            ..Default::default()
+        };
+        let mut session = BlenderSession::new(&options).expect("start session");
+        let input = root.join("a.blend");
+        std::fs::write(&input, "").expect("write blend");
+        for _ in 0..2 {
+            let err = session
+                .convert(&input, &root.join("a"))
+                .expect_err("job raised");
+            assert!(
+                matches!(&err, crate::Error::PythonException { exception, .. } if exception == "RuntimeError: boom"),
+                "{err:?}"
+            );
+        }
+        token.cancel();
+        assert!(matches!(
+            session.convert(&input, &root.join("a")),
+            Err(crate::Error::Cancelled)
+        ));
+        drop(session);
+        std::fs::remove_dir_all(&root).expect("cleanup");
+    }
+}
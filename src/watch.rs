@@ -0,0 +1,69 @@
+//! Picking the blends to convert again out of file system events for
+//! [`ConversionOptions::watch_dir`](crate::ConversionOptions::watch_dir)
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use notify::{Event, EventKind};
+
+/// How long to wait for more events after one arrives. Saving a blend causes several events, and
+/// saving many at once should be converted together.
+pub(crate) const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// The blends `event` may have changed. Blender saves to `<name>.blend@` and renames it over the
+/// blend, so the rename is seen as a change to the blend, while the temporary file and the
+/// `.blend1` backups have other extensions so are ignored. Paths of blends that were renamed away
+/// are included too, callers skip paths that no longer exist.
+pub(crate) fn changed_blends(event: &Event) -> impl Iterator<Item = &Path> {
+    let relevant = !matches!(event.kind, EventKind::Access(_) | EventKind::Remove(_));
+    event
+        .paths
+        .iter()
+        .filter(move |_| relevant)
+        .map(PathBuf::as_path)
+        .filter(|path| path.extension() == Some(OsStr::new("blend")))
+}
+
+/// Maps `path` under the canonical `watched` directory back under `input_dir` as given, so the
+/// output paths match those of [`ConversionOptions::convert_dir`](crate::ConversionOptions::convert_dir)
+pub(crate) fn input_path(input_dir: &Path, watched: &Path, path: &Path) -> PathBuf {
+    match path.strip_prefix(watched) {
+        Ok(relative) => input_dir.join(relative),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use notify::event::{CreateKind, ModifyKind, RemoveKind, RenameMode};
+    use notify::{Event, EventKind};
+
+    #[test]
+    fn blender_saves_are_changes() {
+        let names = |event: &Event| -> Vec<String> {
+            super::changed_blends(event)
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect()
+        };
+        let rename = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path("/w/a.blend@".into())
+            .add_path("/w/a.blend".into());
+        assert_eq!(names(&rename), ["/w/a.blend"]);
+        let backup = Event::new(EventKind::Create(CreateKind::File)).add_path("/w/a.blend1".into());
+        assert!(names(&backup).is_empty());
+        let removed = Event::new(EventKind::Remove(RemoveKind::File)).add_path("/w/a.blend".into());
+        assert!(names(&removed).is_empty());
+
+        assert_eq!(
+            super::input_path(
+                Path::new("blends"),
+                Path::new("/w/blends"),
+                Path::new("/w/blends/props/a.blend")
+            ),
+            Path::new("blends/props/a.blend")
+        );
+    }
+}